@@ -2,6 +2,7 @@ use std::path::Path;
 use std::io::Cursor;
 use ba2::fo4::{Archive, ArchiveKey, FileWriteOptions};
 use ba2::prelude::*;
+use crate::error::{Result, StarDeltaError};
 
 pub struct Ba2Path {
     pub archive_path: String,
@@ -23,19 +24,23 @@ impl Ba2Path {
     }
 }
 
-pub fn extract_file_from_ba2(ba2_path: &Ba2Path) -> Result<Vec<u8>, String> {
+pub fn extract_file_from_ba2(ba2_path: &Ba2Path) -> Result<Vec<u8>> {
     let archive_path = Path::new(&ba2_path.archive_path);
+    let ba2_error = |message: String| StarDeltaError::Ba2 {
+        archive: ba2_path.archive_path.clone(),
+        message,
+    };
 
     // Open and read the archive
     let (archive, meta) = Archive::read(archive_path)
-        .map_err(|e| format!("Failed to open BA2 archive: {}", e))?;
+        .map_err(|e| ba2_error(format!("Failed to open BA2 archive: {}", e)))?;
 
     // Create the archive key from the file path
     let key: ArchiveKey = ba2_path.file_path.as_bytes().into();
 
     // Get the file from the archive
     let file = archive.get(&key)
-        .ok_or_else(|| format!("File '{}' not found in archive", ba2_path.file_path))?;
+        .ok_or_else(|| ba2_error(format!("File '{}' not found in archive", ba2_path.file_path)))?;
 
     // Create a buffer to hold the file data
     let mut buffer = Vec::new();
@@ -45,7 +50,7 @@ pub fn extract_file_from_ba2(ba2_path: &Ba2Path) -> Result<Vec<u8>, String> {
         // Extract the file using the metadata options
         let options: FileWriteOptions = meta.into();
         file.write(&mut cursor, &options)
-            .map_err(|e| format!("Failed to extract file from BA2: {}", e))?;
+            .map_err(|e| ba2_error(format!("Failed to extract file from BA2: {}", e)))?;
     }
 
     Ok(buffer)
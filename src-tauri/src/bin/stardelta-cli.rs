@@ -0,0 +1,183 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use app_lib::error::{Result, StarDeltaError};
+use app_lib::patcher::SwfCompression;
+use app_lib::swf::{self, BatchProcessConfig, SwfMapping};
+use app_lib::xdelta::{self, ApplyPatchArgs, CreatePatchArgs};
+use clap::{Parser, Subcommand};
+
+/// Headless StarDelta: the same patching core as the GUI, without a webview.
+#[derive(Debug, Parser)]
+#[command(name = "stardelta-cli", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    /// Create an xdelta3 patch from an original and an edited file
+    Diff {
+        /// The unmodified file
+        original: PathBuf,
+        /// The modified file
+        edited: PathBuf,
+        /// Directory to write `<original name>.xdelta` into
+        #[arg(short, long)]
+        output_dir: PathBuf,
+    },
+    /// Apply an xdelta3 patch to a file
+    Apply {
+        /// The file to patch
+        file: PathBuf,
+        /// The `.xdelta` patch file
+        patch: PathBuf,
+        /// Directory to write the patched file into
+        #[arg(short, long)]
+        output_dir: PathBuf,
+    },
+    /// Convert an SWF (or `archive.ba2//path/in/archive.swf`) to JSON
+    Swf2json {
+        swf: String,
+        json: PathBuf,
+    },
+    /// Convert an SWF JSON dump back to a binary SWF
+    Json2swf {
+        json: PathBuf,
+        swf: PathBuf,
+        /// Body compression of the written SWF: none, zlib or lzma. JSON dumps don't record
+        /// the original's, so pass the one `swf2json` printed to keep it
+        #[arg(short, long, default_value_t = SwfCompression::None)]
+        compression: SwfCompression,
+    },
+    /// Patch a single SWF with a JSON modification config
+    PatchSwf {
+        /// The SWF to patch (or `archive.ba2//path/in/archive.swf`)
+        swf: String,
+        /// The JSON modification config
+        config: PathBuf,
+        /// Where to write the patched SWF
+        output: PathBuf,
+        /// Also write the patched movie as JSON to this path
+        #[arg(long, value_name = "JSON")]
+        dump_json: Option<PathBuf>,
+        /// Only warn when a modification matches no tags instead of failing
+        #[arg(long)]
+        no_strict: bool,
+    },
+    /// Check a JSON modification config against an SWF without writing anything
+    Validate {
+        /// The SWF to check against (or `archive.ba2//path/in/archive.swf`)
+        swf: String,
+        /// The JSON modification config
+        config: PathBuf,
+    },
+    /// Run every mod in a batch configuration file
+    Batch {
+        /// The batch `configuration.json`
+        config: PathBuf,
+        /// Directory to write the patched SWF files into
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        /// BA2 archive used by mods with `"ba2": true`
+        #[arg(long)]
+        ba2: Option<String>,
+        /// SWF file for a non-BA2 mod, as `"<mod name>=<path to swf>"` (repeatable)
+        #[arg(long = "map", value_name = "MOD=SWF")]
+        mappings: Vec<String>,
+        /// Also write each patched movie as `<file name>.json` into this directory
+        #[arg(long, value_name = "DIR")]
+        dump_json_dir: Option<PathBuf>,
+        /// Only warn when a modification matches no tags instead of failing
+        #[arg(long)]
+        no_strict: bool,
+    },
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| StarDeltaError::config(format!("Invalid file path: {}", path.display())))
+}
+
+fn parse_mapping(mapping: &str) -> Result<SwfMapping> {
+    let (mod_name, swf_path) = mapping.split_once('=').ok_or_else(|| {
+        StarDeltaError::config(format!("Invalid mapping '{}', expected \"<mod name>=<path to swf>\"", mapping))
+    })?;
+    Ok(SwfMapping {
+        mod_name: mod_name.to_string(),
+        swf_path: swf_path.to_string(),
+    })
+}
+
+fn run(cli: Cli) -> Result<()> {
+    match cli.command {
+        Commands::Diff { original, edited, output_dir } => xdelta::create_patch(CreatePatchArgs {
+            original_file_name: file_name(&original)?,
+            original_file_path: original.to_string_lossy().to_string(),
+            edited_file_path: edited.to_string_lossy().to_string(),
+            output_dir: output_dir.to_string_lossy().to_string(),
+        }),
+        Commands::Apply { file, patch, output_dir } => xdelta::apply_patch(ApplyPatchArgs {
+            file_to_patch_name: file_name(&file)?,
+            file_to_patch_path: file.to_string_lossy().to_string(),
+            patch_file_path: patch.to_string_lossy().to_string(),
+            output_dir: output_dir.to_string_lossy().to_string(),
+        }),
+        Commands::Swf2json { swf, json } => {
+            let compression = swf::swf_to_json(&swf, &json.to_string_lossy())?;
+            println!("Source compression: {}", compression);
+            Ok(())
+        }
+        Commands::Json2swf { json, swf, compression } => {
+            swf::json_to_swf(&json.to_string_lossy(), &swf.to_string_lossy(), compression)
+        }
+        Commands::PatchSwf { swf, config, output, dump_json, no_strict } => {
+            let summary = swf::patch_swf_file(&swf, &config, &output, dump_json.as_deref(), !no_strict)?;
+            print!("{}", summary);
+            Ok(())
+        }
+        Commands::Validate { swf, config } => {
+            let report = swf::validate_swf_file(&swf, &config)?;
+            for issue in &report.issues {
+                println!("{}", issue);
+            }
+            if report.is_clean() {
+                println!("No issues found");
+                Ok(())
+            } else {
+                Err(StarDeltaError::config(format!("{} issue(s) found", report.issues.len())))
+            }
+        }
+        Commands::Batch { config, output_dir, ba2, mappings, dump_json_dir, no_strict } => {
+            let config = BatchProcessConfig {
+                config_file: config.to_string_lossy().to_string(),
+                output_directory: output_dir.to_string_lossy().to_string(),
+                ba2_path: ba2,
+                swf_mappings: mappings
+                    .iter()
+                    .map(|m| parse_mapping(m))
+                    .collect::<Result<_>>()?,
+                json_dump_directory: dump_json_dir.map(|dir| dir.to_string_lossy().to_string()),
+                strict: !no_strict,
+            };
+            for file in swf::process_batch(&config)? {
+                println!("{}", file.output_path);
+                print!("{}", file.summary);
+            }
+            Ok(())
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse()) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
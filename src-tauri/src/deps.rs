@@ -0,0 +1,366 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use swf_types::{FillStyle, LineStyle, Movie, Shape, ShapeRecord, Tag};
+
+use crate::error::{Result, StarDeltaError};
+use crate::swf::character_tag;
+use crate::walk::{retain_tags, walk_tags};
+
+/// Which characters reference which, built from every timeline of a `Movie`.
+///
+/// A reference is either containment (a sprite places the character, a button draws it),
+/// which is dropped when the character goes away, or a requirement (a shape's bitmap fill,
+/// a text's font), which makes the referencing character invalid without it.
+#[derive(Debug, Default)]
+pub struct DependencyGraph {
+    /// Character → every character it references
+    uses: HashMap<u16, BTreeSet<u16>>,
+    /// Character → characters that cannot exist without it
+    required_by: HashMap<u16, BTreeSet<u16>>,
+    /// Characters placed on the root timeline or named by `SymbolClass` / `ExportAssets`
+    roots: BTreeSet<u16>,
+    /// Config tag name of every defined character
+    kinds: HashMap<u16, &'static str>,
+}
+
+fn fill_bitmap(fill: &FillStyle) -> Option<u16> {
+    match fill {
+        FillStyle::Bitmap(bitmap) => Some(bitmap.bitmap_id),
+        _ => None,
+    }
+}
+
+fn line_bitmap(line: &LineStyle) -> Option<u16> {
+    fill_bitmap(&line.fill)
+}
+
+/// Bitmaps used by the fill and line styles of a shape, including styles set mid-shape.
+fn shape_bitmaps(shape: &Shape) -> BTreeSet<u16> {
+    let mut styles = vec![&shape.initial_styles];
+    for record in &shape.records {
+        if let ShapeRecord::StyleChange(change) = record {
+            styles.extend(change.new_styles.as_ref());
+        }
+    }
+    styles
+        .into_iter()
+        .flat_map(|s| s.fill.iter().filter_map(fill_bitmap).chain(s.line.iter().filter_map(line_bitmap)))
+        .collect()
+}
+
+/// Every character ID a tag refers to without defining it, including references from the
+/// timeline of a `DefineSprite`.
+pub fn references(tag: &Tag) -> BTreeSet<u16> {
+    let mut ids = BTreeSet::new();
+    walk_tags(std::slice::from_ref(tag), &mut |_, tag| match tag {
+        Tag::PlaceObject(place) => ids.extend(place.character_id),
+        Tag::DefineButton(button) => ids.extend(button.records.iter().map(|r| r.character_id)),
+        Tag::DefineShape(shape) => ids.extend(shape_bitmaps(&shape.shape)),
+        Tag::DefineDynamicText(text) => ids.extend(text.font_id),
+        Tag::DefineText(text) => ids.extend(text.records.iter().filter_map(|r| r.font_id)),
+        Tag::SymbolClass(symbols) => ids.extend(symbols.symbols.iter().map(|s| s.id).filter(|&id| id != 0)),
+        Tag::ExportAssets(exports) => ids.extend(exports.assets.iter().map(|a| a.id)),
+        Tag::DefineFontAlignZones(zones) => {
+            ids.insert(zones.font_id);
+        }
+        _ => {}
+    });
+    ids
+}
+
+impl DependencyGraph {
+    pub fn build(movie: &Movie) -> Self {
+        let mut graph = DependencyGraph::default();
+        walk_tags(&movie.tags, &mut |path, tag| {
+            if let Some((id, kind)) = character_tag(tag) {
+                graph.kinds.insert(id, kind);
+            }
+            match tag {
+                Tag::PlaceObject(place) => {
+                    if let Some(id) = place.character_id {
+                        match path.sprites.last() {
+                            Some(&sprite) => graph.add_use(sprite, id),
+                            None => {
+                                graph.roots.insert(id);
+                            }
+                        }
+                    }
+                }
+                Tag::SymbolClass(symbols) => {
+                    // ID 0 binds the main timeline itself
+                    graph.roots.extend(symbols.symbols.iter().map(|s| s.id).filter(|&id| id != 0));
+                }
+                Tag::ExportAssets(exports) => graph.roots.extend(exports.assets.iter().map(|a| a.id)),
+                Tag::DefineButton(button) => {
+                    for record in &button.records {
+                        graph.add_use(button.id, record.character_id);
+                    }
+                }
+                Tag::DefineShape(shape) => {
+                    for bitmap in shape_bitmaps(&shape.shape) {
+                        graph.add_requirement(shape.id, bitmap);
+                    }
+                }
+                Tag::DefineDynamicText(text) => {
+                    if let Some(font) = text.font_id {
+                        graph.add_requirement(text.id, font);
+                    }
+                }
+                Tag::DefineText(text) => {
+                    for font in text.records.iter().filter_map(|r| r.font_id) {
+                        graph.add_requirement(text.id, font);
+                    }
+                }
+                _ => {}
+            }
+        });
+        graph
+    }
+
+    fn add_use(&mut self, user: u16, used: u16) {
+        self.uses.entry(user).or_default().insert(used);
+    }
+
+    fn add_requirement(&mut self, user: u16, used: u16) {
+        self.add_use(user, used);
+        self.required_by.entry(used).or_default().insert(user);
+    }
+
+    /// Config tag name of a character, if it is defined in the movie.
+    pub fn kind(&self, id: u16) -> Option<&'static str> {
+        self.kinds.get(&id).copied()
+    }
+
+    /// Characters referenced by `id`.
+    pub fn uses(&self, id: u16) -> impl Iterator<Item = u16> + '_ {
+        self.uses.get(&id).into_iter().flatten().copied()
+    }
+
+    /// Characters that become invalid if `id` is removed.
+    pub fn dependents(&self, id: u16) -> impl Iterator<Item = u16> + '_ {
+        self.required_by.get(&id).into_iter().flatten().copied()
+    }
+
+    /// Characters reachable from the roots without passing through `excluded`.
+    pub fn reachable(&self, excluded: &BTreeSet<u16>) -> BTreeSet<u16> {
+        let mut reachable = BTreeSet::new();
+        let mut queue: Vec<u16> = self.roots.iter().copied().filter(|id| !excluded.contains(id)).collect();
+        while let Some(id) = queue.pop() {
+            if reachable.insert(id) {
+                queue.extend(self.uses(id).filter(|used| !excluded.contains(used)));
+            }
+        }
+        reachable
+    }
+}
+
+/// Why a character was removed.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "reason", rename_all = "camelCase")]
+pub enum RemovalReason {
+    /// Listed in `remove_elements`
+    Requested,
+    /// Required a removed character
+    Cascade { dependency: u16 },
+    /// Only used by removed characters
+    Orphaned,
+}
+
+/// A character removed by `remove_elements`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RemovedCharacter {
+    pub id: u16,
+    pub tag: String,
+    #[serde(flatten)]
+    pub reason: RemovalReason,
+}
+
+impl fmt::Display for RemovedCharacter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} ", self.tag, self.id)?;
+        match self.reason {
+            RemovalReason::Requested => f.write_str("(requested)"),
+            RemovalReason::Cascade { dependency } => write!(f, "(required removed character {})", dependency),
+            RemovalReason::Orphaned => f.write_str("(no longer used)"),
+        }
+    }
+}
+
+/// Works out everything to remove along with `requested`. Without `cascade`, removing a
+/// character that others still require is an error.
+pub fn plan_removal(
+    graph: &DependencyGraph,
+    requested: &BTreeSet<u16>,
+    cascade: bool,
+) -> Result<BTreeMap<u16, RemovalReason>> {
+    let mut removed: BTreeMap<u16, RemovalReason> =
+        requested.iter().map(|&id| (id, RemovalReason::Requested)).collect();
+
+    let mut queue: Vec<u16> = requested.iter().copied().collect();
+    while let Some(id) = queue.pop() {
+        let dependents: Vec<u16> = graph.dependents(id).filter(|d| !removed.contains_key(d)).collect();
+        if dependents.is_empty() {
+            continue;
+        }
+        if !cascade {
+            return Err(StarDeltaError::DependentCharacters { id, dependents });
+        }
+        for dependent in dependents {
+            removed.insert(dependent, RemovalReason::Cascade { dependency: id });
+            queue.push(dependent);
+        }
+    }
+
+    if cascade {
+        // Only sweep characters this removal made unreachable, not ones that were already unused
+        let before = graph.reachable(&BTreeSet::new());
+        let excluded: BTreeSet<u16> = removed.keys().copied().collect();
+        let after = graph.reachable(&excluded);
+        for id in before.difference(&after) {
+            removed.entry(*id).or_insert(RemovalReason::Orphaned);
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Removes the definitions of `ids` and every reference to them: placements, button
+/// records, `SymbolClass` and `ExportAssets` entries and per-character metadata tags.
+/// A depth emptied by a removed placement also loses the updates and `RemoveObject` that
+/// follow it, until a new character is placed there.
+pub fn remove_characters(movie: &mut Movie, ids: &BTreeSet<u16>) {
+    // Freed depths of each timeline, keyed by its sprite path
+    let mut freed: HashMap<Vec<u16>, BTreeSet<u16>> = HashMap::new();
+    retain_tags(&mut movie.tags, &mut |path, tag| match tag {
+        Tag::PlaceObject(place) => {
+            let depths = freed.entry(path.sprites.clone()).or_default();
+            match place.character_id {
+                Some(id) if ids.contains(&id) => {
+                    depths.insert(place.depth);
+                    false
+                }
+                Some(_) => {
+                    depths.remove(&place.depth);
+                    true
+                }
+                None => !depths.contains(&place.depth),
+            }
+        }
+        Tag::RemoveObject(remove) => {
+            let depths = freed.entry(path.sprites.clone()).or_default();
+            !(depths.remove(&remove.depth) || remove.character_id.is_some_and(|id| ids.contains(&id)))
+        }
+        Tag::DefineButtonColorTransform(t) => !ids.contains(&t.button_id),
+        Tag::DefineFontAlignZones(t) => !ids.contains(&t.font_id),
+        Tag::DefineFontInfo(t) => !ids.contains(&t.font_id),
+        Tag::DefineFontName(t) => !ids.contains(&t.font_id),
+        Tag::DefineScalingGrid(t) => !ids.contains(&t.character_id),
+        Tag::CsmTextSettings(t) => !ids.contains(&t.text_id),
+        _ => !character_tag(tag).is_some_and(|(id, _)| ids.contains(&id)),
+    });
+
+    // Buttons and symbol tables only appear on the root timeline
+    for tag in &mut movie.tags {
+        match tag {
+            Tag::DefineButton(button) => button.records.retain(|r| !ids.contains(&r.character_id)),
+            Tag::SymbolClass(symbols) => symbols.symbols.retain(|s| !ids.contains(&s.id)),
+            Tag::ExportAssets(exports) => exports.assets.retain(|a| !ids.contains(&a.id)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::{movie, tag_list};
+
+    /// Root sprite 1 places sprite 2 and shape 4, sprite 2 places shape 3 and shape 4, and
+    /// text 5 on the root timeline needs font 6.
+    fn graph() -> DependencyGraph {
+        let mut graph = DependencyGraph::default();
+        graph.roots.extend([1, 5]);
+        graph.add_use(1, 2);
+        graph.add_use(1, 4);
+        graph.add_use(2, 3);
+        graph.add_use(2, 4);
+        graph.add_requirement(5, 6);
+        graph
+    }
+
+    #[test]
+    fn removing_a_required_character_needs_cascade() {
+        let err = plan_removal(&graph(), &BTreeSet::from([6]), false).unwrap_err();
+        assert!(matches!(err, StarDeltaError::DependentCharacters { id: 6, ref dependents } if dependents == &[5]));
+
+        let removed = plan_removal(&graph(), &BTreeSet::from([6]), true).unwrap();
+        assert_eq!(removed.keys().copied().collect::<Vec<_>>(), [5, 6]);
+        assert!(matches!(removed[&5], RemovalReason::Cascade { dependency: 6 }));
+    }
+
+    #[test]
+    fn cascade_sweeps_characters_only_used_by_removed_ones() {
+        let mut graph = graph();
+        // Already unused before the removal, so left alone
+        graph.add_use(7, 3);
+
+        let removed = plan_removal(&graph, &BTreeSet::from([2]), true).unwrap();
+        assert_eq!(removed.keys().copied().collect::<Vec<_>>(), [2, 3]);
+        assert!(matches!(removed[&2], RemovalReason::Requested));
+        assert!(matches!(removed[&3], RemovalReason::Orphaned));
+
+        // Without cascade nothing else is removed
+        let removed = plan_removal(&graph, &BTreeSet::from([2]), false).unwrap();
+        assert_eq!(removed.keys().copied().collect::<Vec<_>>(), [2]);
+    }
+
+    #[test]
+    fn removal_drops_updates_of_freed_depths() {
+        let mut movie = movie(tag_list(json!([
+            {
+                "type": "DefineSprite",
+                "id": 1,
+                "frame_count": 3,
+                "tags": [
+                    { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 3 },
+                    { "type": "PlaceObject", "is_update": false, "depth": 2, "character_id": 4 },
+                    { "type": "ShowFrame" },
+                    { "type": "PlaceObject", "is_update": true, "depth": 1, "ratio": 2 },
+                    { "type": "PlaceObject", "is_update": true, "depth": 2, "ratio": 2 },
+                    { "type": "RemoveObject", "depth": 1 },
+                    { "type": "ShowFrame" },
+                    { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 4 },
+                    { "type": "RemoveObject", "depth": 1 },
+                    { "type": "ShowFrame" }
+                ]
+            },
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 1 },
+            { "type": "ShowFrame" }
+        ])));
+        remove_characters(&mut movie, &BTreeSet::from([3]));
+
+        let expected = tag_list(json!([
+            {
+                "type": "DefineSprite",
+                "id": 1,
+                "frame_count": 3,
+                "tags": [
+                    { "type": "PlaceObject", "is_update": false, "depth": 2, "character_id": 4 },
+                    { "type": "ShowFrame" },
+                    { "type": "PlaceObject", "is_update": true, "depth": 2, "ratio": 2 },
+                    { "type": "ShowFrame" },
+                    { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 4 },
+                    { "type": "RemoveObject", "depth": 1 },
+                    { "type": "ShowFrame" }
+                ]
+            },
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 1 },
+            { "type": "ShowFrame" }
+        ]));
+        assert_eq!(serde_json::to_value(&movie.tags).unwrap(), serde_json::to_value(&expected).unwrap());
+    }
+}
@@ -0,0 +1,231 @@
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use swf_types::{tags, text, Movie, Rect, StraightSRgba8};
+
+use crate::error::{Result, StarDeltaError};
+use crate::ids::Aliases;
+use crate::target::CharacterRef;
+
+/// Property names understood by [`EditTextProperties`], as written in modifications.
+pub const EDIT_TEXT_PROPERTIES: &[&str] = &[
+    "text", "htmlText", "html", "bounds", "fontId", "fontClass", "fontSize", "color", "autoSize", "border",
+    "password", "readonly", "multiline", "wordWrap", "noSelect", "useOutlines", "maxLength", "variableName",
+    "align", "marginLeft", "marginRight", "indent", "leading",
+];
+
+/// Reads a present field as `Some`, so `null` can be told apart from a missing field.
+fn present<'de, D: Deserializer<'de>, T: Deserialize<'de>>(deserializer: D) -> std::result::Result<Option<T>, D::Error> {
+    T::deserialize(deserializer).map(Some)
+}
+
+/// Properties of a `DefineDynamicTextTag` (`DefineEditText`) modification. Fields left out
+/// keep their value; `null` clears the optional ones (`fontId`, `maxLength`, ...).
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditTextProperties {
+    #[serde(default, deserialize_with = "present")]
+    pub text: Option<Option<String>>,
+    pub html_text: Option<String>,         // Sets `text` and turns `html` on
+    pub html: Option<bool>,
+    pub bounds: Option<Rect>,
+    #[serde(default, deserialize_with = "present")]
+    pub font_id: Option<Option<CharacterRef>>,  // ID, "@alias" or {"font": "name"}
+    #[serde(default, deserialize_with = "present")]
+    pub font_class: Option<Option<String>>,
+    pub font_size: Option<u16>,            // In twips
+    #[serde(default, deserialize_with = "present")]
+    pub color: Option<Option<StraightSRgba8>>,
+    pub auto_size: Option<bool>,
+    pub border: Option<bool>,
+    pub password: Option<bool>,
+    pub readonly: Option<bool>,
+    pub multiline: Option<bool>,
+    pub word_wrap: Option<bool>,
+    pub no_select: Option<bool>,
+    pub use_outlines: Option<bool>,
+    #[serde(default, deserialize_with = "present")]
+    pub max_length: Option<Option<u16>>,
+    #[serde(default, deserialize_with = "present")]
+    pub variable_name: Option<Option<String>>,
+    pub align: Option<text::TextAlignment>,
+    pub margin_left: Option<u16>,
+    pub margin_right: Option<u16>,
+    pub indent: Option<u16>,
+    pub leading: Option<i16>,
+}
+
+impl EditTextProperties {
+    /// Reads the `properties` of a modification, naming the offending property on error.
+    pub fn from_properties(tag: &str, properties: &Value) -> Result<Self> {
+        serde_path_to_error::deserialize(properties.clone())
+            .map_err(|e| StarDeltaError::invalid_property(tag, e.path().to_string(), e.inner()))
+    }
+
+    /// Resolves `fontId` to a font ID, `Some(None)` if it clears the font.
+    pub fn resolve_font(&self, movie: &Movie, aliases: &Aliases) -> Result<Option<Option<u16>>> {
+        match &self.font_id {
+            Some(Some(font)) => Ok(Some(Some(font.resolve(movie, aliases)?))),
+            Some(None) => Ok(Some(None)),
+            None => Ok(None),
+        }
+    }
+
+    /// Sets every given property on `tag`, with `font_id` from [`EditTextProperties::resolve_font`].
+    pub fn apply(&self, tag: &mut tags::DefineDynamicText, font_id: Option<Option<u16>>) -> Result<()> {
+        if let Some(text) = &self.text {
+            tag.text = text.clone();
+        }
+        if let Some(html_text) = &self.html_text {
+            tag.text = Some(html_text.clone());
+            tag.html = true;
+        }
+        if let Some(html) = self.html {
+            tag.html = html;
+        }
+        if let Some(bounds) = &self.bounds {
+            tag.bounds = bounds.clone();
+        }
+        // A field uses either an embedded font or an ActionScript font class, never both
+        if let Some(font_id) = font_id {
+            tag.font_id = font_id;
+            if font_id.is_some() {
+                tag.font_class = None;
+            }
+        }
+        if let Some(font_class) = &self.font_class {
+            tag.font_class = font_class.clone();
+            if font_class.is_some() {
+                tag.font_id = None;
+            }
+        }
+        if let Some(font_size) = self.font_size {
+            tag.font_size = Some(font_size);
+        }
+        if let Some(color) = self.color {
+            tag.color = color;
+        }
+        set(&mut tag.auto_size, self.auto_size);
+        set(&mut tag.border, self.border);
+        set(&mut tag.password, self.password);
+        set(&mut tag.readonly, self.readonly);
+        set(&mut tag.multiline, self.multiline);
+        set(&mut tag.word_wrap, self.word_wrap);
+        set(&mut tag.no_select, self.no_select);
+        set(&mut tag.use_glyph_font, self.use_outlines);
+        if let Some(max_length) = self.max_length {
+            tag.max_length = max_length.map(Into::into);
+        }
+        if let Some(variable_name) = &self.variable_name {
+            tag.variable_name = variable_name.clone();
+        }
+        set(&mut tag.align, self.align);
+        set(&mut tag.margin_left, self.margin_left);
+        set(&mut tag.margin_right, self.margin_right);
+        set(&mut tag.indent, self.indent);
+        set(&mut tag.leading, self.leading);
+        check_font(tag)
+    }
+}
+
+fn set<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
+
+/// A text field with a font must give its size, or the emitted tag has no font height.
+pub fn check_font(tag: &tags::DefineDynamicText) -> Result<()> {
+    if (tag.font_id.is_some() || tag.font_class.is_some()) && tag.font_size.is_none() {
+        return Err(StarDeltaError::invalid_property(
+            "DefineDynamicTextTag",
+            "fontSize",
+            format!("text {} has a font but no font size", tag.id),
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::movie;
+
+    /// A read-only field showing "Old" in font 2 at 12 px.
+    fn dynamic_text() -> tags::DefineDynamicText {
+        tags::DefineDynamicText {
+            id: 4,
+            bounds: Rect {
+                x_min: 0,
+                x_max: 2000,
+                y_min: 0,
+                y_max: 400,
+            },
+            word_wrap: false,
+            multiline: false,
+            password: false,
+            readonly: true,
+            auto_size: false,
+            no_select: false,
+            border: false,
+            was_static: false,
+            html: false,
+            use_glyph_font: false,
+            font_id: Some(2),
+            font_class: None,
+            font_size: Some(240),
+            color: None,
+            max_length: None,
+            align: text::TextAlignment::Left,
+            margin_left: 0,
+            margin_right: 0,
+            indent: 0,
+            leading: 0,
+            variable_name: None,
+            text: Some("Old".to_string()),
+        }
+    }
+
+    fn apply(tag: &mut tags::DefineDynamicText, properties: Value) -> Result<()> {
+        let properties = EditTextProperties::from_properties("DefineDynamicTextTag", &properties)?;
+        let font_id = properties.resolve_font(&movie(Vec::new()), &Aliases::default())?;
+        properties.apply(tag, font_id)
+    }
+
+    fn invalid_property(err: StarDeltaError) -> String {
+        match err {
+            StarDeltaError::InvalidProperty { property, .. } => property,
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn html_text_and_font_class_replace_text_and_font() {
+        let mut field = dynamic_text();
+        apply(&mut field, json!({ "htmlText": "<b>New</b>", "fontClass": "$Main", "maxLength": 20 })).unwrap();
+        assert_eq!(field.text.as_deref(), Some("<b>New</b>"));
+        assert!(field.html);
+        assert_eq!(field.font_class.as_deref(), Some("$Main"));
+        assert_eq!(field.font_id, None);
+        assert_eq!(field.max_length, Some(20));
+        assert!(field.readonly);
+    }
+
+    #[test]
+    fn null_clears_text_and_font() {
+        let mut field = dynamic_text();
+        apply(&mut field, json!({ "text": null, "fontId": null })).unwrap();
+        assert_eq!(field.text, None);
+        assert_eq!(field.font_id, None);
+        assert_eq!(field.font_size, Some(240));
+    }
+
+    #[test]
+    fn a_font_needs_a_size() {
+        let mut field = dynamic_text();
+        field.font_size = None;
+        assert_eq!(invalid_property(apply(&mut field, json!({ "text": "New" })).unwrap_err()), "fontSize");
+        assert_eq!(invalid_property(apply(&mut field, json!({ "fontSize": "big" })).unwrap_err()), "fontSize");
+    }
+}
@@ -0,0 +1,257 @@
+use serde::de::DeserializeOwned;
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::{json, Value};
+use std::io;
+use thiserror::Error;
+
+pub type Result<T, E = StarDeltaError> = std::result::Result<T, E>;
+
+/// Every failure the patching core can report.
+///
+/// Serialized to the frontend as `{ "kind": "...", "message": "...", ...details }` so the
+/// UI and scripts can branch on `kind` while still having a readable message.
+#[derive(Debug, Error)]
+pub enum StarDeltaError {
+    #[error("{context} '{path}': {source}")]
+    Io {
+        context: String,
+        path: String,
+        #[source]
+        source: io::Error,
+    },
+
+    #[error("Failed to parse SWF: {message}")]
+    SwfParse { message: String },
+
+    #[error("Failed to emit SWF: {message}")]
+    SwfEmit { message: String },
+
+    #[error("BA2 archive '{archive}': {message}")]
+    Ba2 { archive: String, message: String },
+
+    #[error("Failed to parse '{file}' at {json_path} (line {line}, column {column}): {message}")]
+    ConfigParse {
+        file: String,
+        json_path: String,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    #[error("Failed to serialize JSON: {message}")]
+    JsonSerialize { message: String },
+
+    #[error("{message}")]
+    Config { message: String },
+
+    #[error("{} modification(s) matched no tags: {}", .locations.len(), .locations.join(", "))]
+    UnmatchedModifications { locations: Vec<String> },
+
+    #[error("JSON patch failed at '{path}': {message}")]
+    JsonPatch {
+        operation: Option<usize>,
+        path: String,
+        message: String,
+    },
+
+    #[error("No character matches {target}")]
+    UnresolvedTarget { target: String },
+
+    #[error("Cannot remove character {id}: still required by {dependents:?} (set remove_elements.cascade to remove them too)")]
+    DependentCharacters { id: u16, dependents: Vec<u16> },
+
+    #[error("{location}: character ID {id} is already used by {existing}")]
+    IdCollision {
+        id: u16,
+        location: String,
+        existing: String,
+    },
+
+    #[error("{location}: alias '@{alias}' is already defined")]
+    DuplicateAlias { alias: String, location: String },
+
+    #[error("{tag} with ID {id} not found")]
+    MissingCharacter { tag: String, id: u16 },
+
+    #[error("Invalid '{property}' for {tag}: {message}")]
+    InvalidProperty {
+        tag: String,
+        property: String,
+        message: String,
+    },
+
+    #[error("Failed to import SVG '{path}': {message}")]
+    Svg { path: String, message: String },
+
+    #[error("Failed to embed font '{path}': {message}")]
+    Font { path: String, message: String },
+
+    #[error("Java is not installed or not accessible")]
+    JavaNotFound,
+
+    #[error("ActionScript compilation failed: {output}")]
+    ActionScriptCompile { output: String },
+
+    #[error("xdelta3 {operation} failed")]
+    Xdelta { operation: String },
+}
+
+impl StarDeltaError {
+    pub fn io(context: impl Into<String>, path: impl AsRef<std::path::Path>, source: io::Error) -> Self {
+        StarDeltaError::Io {
+            context: context.into(),
+            path: path.as_ref().display().to_string(),
+            source,
+        }
+    }
+
+    pub fn config(message: impl Into<String>) -> Self {
+        StarDeltaError::Config { message: message.into() }
+    }
+
+    pub fn missing(tag: impl Into<String>, id: u16) -> Self {
+        StarDeltaError::MissingCharacter { tag: tag.into(), id }
+    }
+
+    pub fn invalid_property(tag: impl Into<String>, property: impl Into<String>, message: impl ToString) -> Self {
+        StarDeltaError::InvalidProperty {
+            tag: tag.into(),
+            property: property.into(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn svg(path: impl AsRef<std::path::Path>, message: impl ToString) -> Self {
+        StarDeltaError::Svg {
+            path: path.as_ref().display().to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    pub fn font(path: impl AsRef<std::path::Path>, message: impl ToString) -> Self {
+        StarDeltaError::Font {
+            path: path.as_ref().display().to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Stable identifier for the variant, used as the `kind` field on the frontend.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StarDeltaError::Io { .. } => "io",
+            StarDeltaError::SwfParse { .. } => "swfParse",
+            StarDeltaError::SwfEmit { .. } => "swfEmit",
+            StarDeltaError::Ba2 { .. } => "ba2",
+            StarDeltaError::ConfigParse { .. } => "configParse",
+            StarDeltaError::JsonSerialize { .. } => "jsonSerialize",
+            StarDeltaError::Config { .. } => "config",
+            StarDeltaError::UnmatchedModifications { .. } => "unmatchedModifications",
+            StarDeltaError::JsonPatch { .. } => "jsonPatch",
+            StarDeltaError::UnresolvedTarget { .. } => "unresolvedTarget",
+            StarDeltaError::DependentCharacters { .. } => "dependentCharacters",
+            StarDeltaError::IdCollision { .. } => "idCollision",
+            StarDeltaError::DuplicateAlias { .. } => "duplicateAlias",
+            StarDeltaError::MissingCharacter { .. } => "missingCharacter",
+            StarDeltaError::InvalidProperty { .. } => "invalidProperty",
+            StarDeltaError::Svg { .. } => "svg",
+            StarDeltaError::Font { .. } => "font",
+            StarDeltaError::JavaNotFound => "javaNotFound",
+            StarDeltaError::ActionScriptCompile { .. } => "actionScriptCompile",
+            StarDeltaError::Xdelta { .. } => "xdelta",
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            StarDeltaError::Io { context, path, source } => {
+                json!({ "context": context, "path": path, "ioKind": format!("{:?}", source.kind()) })
+            }
+            StarDeltaError::SwfParse { .. }
+            | StarDeltaError::SwfEmit { .. }
+            | StarDeltaError::JsonSerialize { .. }
+            | StarDeltaError::Config { .. }
+            | StarDeltaError::JavaNotFound => json!({}),
+            StarDeltaError::Ba2 { archive, .. } => json!({ "archive": archive }),
+            StarDeltaError::ConfigParse { file, json_path, line, column, .. } => {
+                json!({ "file": file, "jsonPath": json_path, "line": line, "column": column })
+            }
+            StarDeltaError::UnmatchedModifications { locations } => json!({ "locations": locations }),
+            StarDeltaError::JsonPatch { operation, path, .. } => {
+                json!({ "operation": operation, "path": path })
+            }
+            StarDeltaError::UnresolvedTarget { target } => json!({ "target": target }),
+            StarDeltaError::DependentCharacters { id, dependents } => {
+                json!({ "id": id, "dependents": dependents })
+            }
+            StarDeltaError::IdCollision { id, location, existing } => {
+                json!({ "id": id, "location": location, "existing": existing })
+            }
+            StarDeltaError::DuplicateAlias { alias, location } => json!({ "alias": alias, "location": location }),
+            StarDeltaError::MissingCharacter { tag, id } => json!({ "tag": tag, "id": id }),
+            StarDeltaError::InvalidProperty { tag, property, .. } => {
+                json!({ "tag": tag, "property": property })
+            }
+            StarDeltaError::Svg { path, .. } | StarDeltaError::Font { path, .. } => json!({ "path": path }),
+            StarDeltaError::ActionScriptCompile { output } => json!({ "output": output }),
+            StarDeltaError::Xdelta { operation } => json!({ "operation": operation }),
+        }
+    }
+}
+
+impl Serialize for StarDeltaError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let details = self.details();
+        let details = details.as_object();
+        let mut map = serializer.serialize_map(Some(2 + details.map_or(0, |d| d.len())))?;
+        map.serialize_entry("kind", self.kind())?;
+        map.serialize_entry("message", &self.to_string())?;
+        if let Some(details) = details {
+            for (key, value) in details {
+                map.serialize_entry(key, value)?;
+            }
+        }
+        map.end()
+    }
+}
+
+/// Deserializes JSON, reporting the JSON path and position of the first mismatch.
+pub fn from_json_str<T: DeserializeOwned>(json: &str, file: &str) -> Result<T> {
+    let deserializer = &mut serde_json::Deserializer::from_str(json);
+    serde_path_to_error::deserialize(deserializer).map_err(|e| {
+        let json_path = e.path().to_string();
+        let inner = e.into_inner();
+        StarDeltaError::ConfigParse {
+            file: file.to_string(),
+            json_path,
+            line: inner.line(),
+            column: inner.column(),
+            message: inner.to_string(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn errors_serialize_with_kind_message_and_details() {
+        let value = serde_json::to_value(StarDeltaError::missing("DefineShapeTag", 3)).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "kind": "missingCharacter",
+                "message": "DefineShapeTag with ID 3 not found",
+                "tag": "DefineShapeTag",
+                "id": 3
+            })
+        );
+    }
+
+    #[test]
+    fn config_parse_errors_name_the_json_path() {
+        let err = from_json_str::<HashMap<String, Vec<u16>>>("{\n  \"ids\": [1, \"two\"]\n}", "config.json").unwrap_err();
+        assert!(matches!(err, StarDeltaError::ConfigParse { ref json_path, line: 2, .. } if json_path == "ids[1]"));
+    }
+}
@@ -0,0 +1,437 @@
+use kurbo::{CubicBez, Point};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use swf_types::{shape_records, tags, text, Glyph, LanguageCode, Movie, Rect, ShapeRecord, Tag, Vector2D};
+use ttf_parser::{kern, name_id, Face, OutlineBuilder};
+
+use crate::error::{Result, StarDeltaError};
+use crate::walk::walk_tags;
+
+/// DefineFont3 glyphs are drawn on a 1024 unit em square at 20 twips per unit.
+const EM_SQUARE: f64 = 20480.0;
+/// Largest distance, in em square units, between a cubic outline and its quadratic curves.
+const CUBIC_ACCURACY: f64 = 2.0;
+
+/// A TrueType or OpenType font file to embed.
+pub struct FontFile {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+/// Tags that embed a font: the `DefineFont3` and its `DefineFontAlignZones`.
+pub struct EmbeddedFont {
+    pub font: tags::DefineFont,
+    pub zones: tags::DefineFontAlignZones,
+    pub missing: Vec<char>,  // Requested characters the file has no glyph for
+}
+
+impl FontFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(|e| StarDeltaError::io("Failed to read font", path, e))?;
+        let file = FontFile {
+            path: path.to_path_buf(),
+            data,
+        };
+        file.face()?;
+        Ok(file)
+    }
+
+    fn face(&self) -> Result<Face<'_>> {
+        Face::parse(&self.data, 0).map_err(|e| StarDeltaError::font(&self.path, e))
+    }
+
+    /// Family name stored in the file.
+    pub fn family_name(&self) -> Result<String> {
+        self.face()?
+            .names()
+            .into_iter()
+            .filter(|name| name.name_id == name_id::FAMILY)
+            .find_map(|name| name.to_string())
+            .ok_or_else(|| StarDeltaError::font(&self.path, "the file has no family name, set 'name'"))
+    }
+
+    /// Converts the glyphs of `chars` to a `DefineFont3` with advances, kerning and
+    /// alignment zones. `bold` and `italic` default to the style in the file.
+    pub fn embed(
+        &self,
+        id: u16,
+        name: &str,
+        bold: Option<bool>,
+        italic: Option<bool>,
+        chars: &BTreeSet<char>,
+    ) -> Result<EmbeddedFont> {
+        let face = self.face()?;
+        let scale = EM_SQUARE / f64::from(face.units_per_em());
+        let to_em = |value: f64| (value * scale).round();
+        // Glyphs are filled on the left, as CFF outlines run; TrueType outlines run the other way
+        let reverse = face.tables().glyf.is_some();
+
+        let mut missing = Vec::new();
+        let mut embedded = Vec::new();
+        let mut glyphs = Vec::new();
+        let mut advances = Vec::new();
+        let mut bounds = Vec::new();
+        let mut zones = Vec::new();
+        for &c in chars {
+            // Code units are UTF-16, so only characters of the Basic Multilingual Plane fit
+            let (Some(glyph_id), Ok(code_unit)) = (face.glyph_index(c), u16::try_from(u32::from(c))) else {
+                missing.push(c);
+                continue;
+            };
+            let mut builder = GlyphBuilder::new(scale);
+            let glyph_bounds = face.outline_glyph(glyph_id, &mut builder).map_or(
+                Rect {
+                    x_min: 0,
+                    x_max: 0,
+                    y_min: 0,
+                    y_max: 0,
+                },
+                |b| Rect {
+                    x_min: to_em(f64::from(b.x_min)) as i32,
+                    x_max: to_em(f64::from(b.x_max)) as i32,
+                    y_min: -to_em(f64::from(b.y_max)) as i32,
+                    y_max: -to_em(f64::from(b.y_min)) as i32,
+                },
+            );
+            let advance = face.glyph_hor_advance(glyph_id).unwrap_or(0);
+            embedded.push((code_unit, glyph_id));
+            glyphs.push(Glyph { records: builder.finish(reverse) });
+            advances.push(to_em(f64::from(advance)).clamp(0.0, f64::from(u16::MAX)) as u16);
+            zones.push(alignment_zone(&glyph_bounds));
+            bounds.push(glyph_bounds);
+        }
+
+        // Only the pair lists of the legacy `kern` table are read; GPOS kerning and the
+        // class-based Apple formats are not carried over. The first subtable with a pair wins.
+        let mut code_units: HashMap<u16, Vec<u16>> = HashMap::new();
+        for &(code_unit, glyph_id) in &embedded {
+            code_units.entry(glyph_id.0).or_default().push(code_unit);
+        }
+        let mut adjustments = BTreeMap::new();
+        for subtable in face.tables().kern.into_iter().flat_map(|kern| kern.subtables) {
+            let kern::Format::Format0(table) = subtable.format else {
+                continue;
+            };
+            if !subtable.horizontal || subtable.variable {
+                continue;
+            }
+            for pair in table.pairs {
+                let (Some(lefts), Some(rights)) = (code_units.get(&pair.left().0), code_units.get(&pair.right().0)) else {
+                    continue;
+                };
+                for &left in lefts {
+                    for &right in rights {
+                        adjustments.entry((left, right)).or_insert(pair.value);
+                    }
+                }
+            }
+        }
+        let kerning = adjustments
+            .into_iter()
+            .filter(|&(_, adjustment)| adjustment != 0)
+            .map(|((left, right), adjustment)| text::KerningRecord {
+                left,
+                right,
+                adjustment: to_em(f64::from(adjustment)) as i16,
+            })
+            .collect();
+
+        let font = tags::DefineFont {
+            id,
+            font_name: name.to_string(),
+            is_bold: bold.unwrap_or(face.is_bold()),
+            is_italic: italic.unwrap_or(face.is_italic()),
+            is_ansi: false,
+            is_small: false,
+            is_shift_jis: false,
+            em_square_size: text::EmSquareSize::EmSquareSize20480,
+            language: LanguageCode::Auto,
+            glyphs: Some(glyphs),
+            code_units: Some(embedded.iter().map(|&(code_unit, _)| code_unit).collect()),
+            layout: Some(text::FontLayout {
+                ascent: to_em(f64::from(face.ascender())).max(0.0) as u16,
+                descent: to_em(-f64::from(face.descender())).max(0.0) as u16,
+                leading: to_em(f64::from(face.line_gap())).max(0.0) as u16,
+                advances,
+                bounds,
+                kerning,
+            }),
+        };
+        let zones = tags::DefineFontAlignZones {
+            font_id: id,
+            csm_table_hint: text::CsmTableHint::Thin,
+            zones,
+        };
+        Ok(EmbeddedFont { font, zones, missing })
+    }
+}
+
+/// Horizontal and vertical alignment zone of a glyph, in fractions of the em square.
+fn alignment_zone(bounds: &Rect) -> text::FontAlignmentZone {
+    let zone = |min: i32, max: i32| text::FontAlignmentZoneData {
+        origin: (f64::from(min) / EM_SQUARE) as f32,
+        size: (f64::from(max - min) / EM_SQUARE) as f32,
+    };
+    text::FontAlignmentZone {
+        data: vec![zone(bounds.x_min, bounds.x_max), zone(bounds.y_min, bounds.y_max)],
+        has_x: true,
+        has_y: true,
+    }
+}
+
+/// A closed glyph contour: where it starts, then each straight or quadratic segment as its
+/// control point and end point, all unrounded.
+struct Contour {
+    start: Point,
+    segments: Vec<(Option<Point>, Point)>,
+}
+
+impl Contour {
+    fn end(&self) -> Point {
+        self.segments.last().map_or(self.start, |&(_, to)| to)
+    }
+
+    /// The same outline drawn the other way round, which puts the fill on the other side.
+    fn reversed(self) -> Contour {
+        let mut segments = Vec::with_capacity(self.segments.len());
+        for i in (0..self.segments.len()).rev() {
+            let from = if i == 0 { self.start } else { self.segments[i - 1].1 };
+            segments.push((self.segments[i].0, from));
+        }
+        Contour {
+            start: self.end(),
+            segments,
+        }
+    }
+}
+
+/// Collects a glyph outline on the em square, with y pointing down.
+struct GlyphBuilder {
+    scale: f64,
+    contours: Vec<Contour>,
+}
+
+impl GlyphBuilder {
+    fn new(scale: f64) -> Self {
+        GlyphBuilder {
+            scale,
+            contours: Vec::new(),
+        }
+    }
+
+    fn point(&self, x: f32, y: f32) -> Point {
+        Point::new(f64::from(x) * self.scale, -f64::from(y) * self.scale)
+    }
+
+    fn segment(&mut self, control: Option<Point>, to: Point) {
+        if let Some(contour) = self.contours.last_mut() {
+            contour.segments.push((control, to));
+        }
+    }
+
+    /// Shape records for the outline, every contour reversed if `reverse`. Glyphs have no
+    /// style arrays: fill 1 is the glyph fill, set once on the left of the first contour,
+    /// since players only draw glyphs with their first fill style.
+    fn finish(mut self, reverse: bool) -> Vec<ShapeRecord> {
+        self.close();
+        let round = |p: Point| (p.x.round() as i32, p.y.round() as i32);
+        let mut records = Vec::new();
+        for contour in self.contours {
+            let contour = if reverse { contour.reversed() } else { contour };
+            let mut pen = round(contour.start);
+            records.push(ShapeRecord::StyleChange(shape_records::StyleChange {
+                move_to: Some(Vector2D { x: pen.0, y: pen.1 }),
+                left_fill: records.is_empty().then_some(1),
+                right_fill: None,
+                line_style: None,
+                new_styles: None,
+            }));
+            for (control, to) in contour.segments {
+                let end = round(to);
+                if control.is_none() && end == pen {
+                    continue;
+                }
+                let delta = |(x, y): (i32, i32)| Vector2D {
+                    x: x - pen.0,
+                    y: y - pen.1,
+                };
+                records.push(ShapeRecord::Edge(shape_records::Edge {
+                    delta: delta(end),
+                    control_delta: control.map(|c| delta(round(c))),
+                }));
+                pen = end;
+            }
+        }
+        records
+    }
+}
+
+impl OutlineBuilder for GlyphBuilder {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+        let start = self.point(x, y);
+        self.contours.push(Contour {
+            start,
+            segments: Vec::new(),
+        });
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let to = self.point(x, y);
+        self.segment(None, to);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let control = self.point(x1, y1);
+        let to = self.point(x, y);
+        self.segment(Some(control), to);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let Some(from) = self.contours.last().map(Contour::end) else {
+            return;
+        };
+        let cubic = CubicBez::new(from, self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+        for (_, _, quad) in cubic.to_quads(CUBIC_ACCURACY) {
+            self.segment(Some(quad.p1), quad.p2);
+        }
+    }
+
+    fn close(&mut self) {
+        if let Some(contour) = self.contours.last_mut() {
+            if contour.end() != contour.start {
+                contour.segments.push((None, contour.start));
+            }
+        }
+    }
+}
+
+/// Reads a `chars` list: `"U+0400-U+04FF"` ranges, `"U+00E9"` code points, and any other
+/// string as the literal characters it contains.
+pub fn parse_char_set(entries: &[String], location: &str) -> Result<BTreeSet<char>> {
+    let mut chars = BTreeSet::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let range = entry
+            .split_once('-')
+            .and_then(|(from, to)| Some((code_point(from)?, code_point(to)?)));
+        if let Some((from, to)) = range {
+            if from > to {
+                return Err(StarDeltaError::config(format!(
+                    "{}[{}]: range '{}' ends before it starts",
+                    location, i, entry
+                )));
+            }
+            chars.extend(from..=to);
+        } else if let Some(c) = code_point(entry) {
+            chars.insert(c);
+        } else {
+            chars.extend(entry.chars());
+        }
+    }
+    Ok(chars)
+}
+
+fn code_point(s: &str) -> Option<char> {
+    let hex = s.trim().strip_prefix("U+").or_else(|| s.trim().strip_prefix("u+"))?;
+    char::from_u32(u32::from_str_radix(hex, 16).ok()?)
+}
+
+/// Every character shown by a text field bound to font `font_id`, without HTML markup.
+pub fn used_chars(movie: &Movie, font_id: u16) -> BTreeSet<char> {
+    let mut chars = BTreeSet::new();
+    walk_tags(&movie.tags, &mut |_, tag| {
+        if let Tag::DefineDynamicText(field) = tag {
+            if let Some(text) = field.text.as_ref().filter(|_| field.font_id == Some(font_id)) {
+                if field.html {
+                    chars.extend(html_text(text).chars());
+                } else {
+                    chars.extend(text.chars());
+                }
+            }
+        }
+    });
+    chars.retain(|c| !c.is_control());
+    chars
+}
+
+/// Text content of an HTML text field: tags dropped, entities decoded.
+fn html_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find(['<', '&']) {
+        text.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest.starts_with('<') {
+            rest = rest.find('>').map_or("", |end| &rest[end + 1..]);
+            continue;
+        }
+        let entity = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                text.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some('\u{a0}'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn char_set(entries: &[&str]) -> Result<BTreeSet<char>> {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        parse_char_set(&entries, "chars")
+    }
+
+    #[test]
+    fn char_set_reads_ranges_code_points_and_literals() {
+        let chars = char_set(&["U+0041-U+0043", "u+00E9", "xy", "a-b"]).unwrap();
+        assert_eq!(chars.into_iter().collect::<String>(), "-ABCabxyé");
+    }
+
+    #[test]
+    fn char_set_rejects_reversed_range() {
+        assert!(char_set(&["U+0043-U+0041"]).is_err());
+    }
+
+    #[test]
+    fn html_text_drops_tags_and_decodes_entities() {
+        let html = r#"<p align="left"><font face="$Main">A &amp; B&#233;&#x41;&nbsp;</font></p>"#;
+        assert_eq!(html_text(html), "A & BéA\u{a0}");
+    }
+
+    #[test]
+    fn html_text_keeps_unknown_entities() {
+        assert_eq!(html_text("AT&T &bogus; &lt;"), "AT&T &bogus; <");
+    }
+}
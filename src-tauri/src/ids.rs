@@ -0,0 +1,281 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use swf_types::{Movie, Tag};
+
+use crate::error::{Result, StarDeltaError};
+use crate::swf::character_tag;
+use crate::walk::walk_tags;
+
+/// A character ID picked for a new element that did not set one.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssignedId {
+    pub location: String,
+    pub tag: String,
+    pub id: u16,
+}
+
+impl fmt::Display for AssignedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({}) -> {}", self.location, self.tag, self.id)
+    }
+}
+
+/// IDs of new elements by their `alias`, for `"@alias"` references elsewhere in a config.
+#[derive(Debug, Default)]
+pub struct Aliases(HashMap<String, u16>);
+
+impl Aliases {
+    /// ID of the new element aliased `name` (without the `@`).
+    pub fn get(&self, name: &str) -> Result<u16> {
+        self.0.get(name).copied().ok_or_else(|| StarDeltaError::UnresolvedTarget {
+            target: format!("alias '@{}'", name),
+        })
+    }
+
+    /// Replaces every `"@alias"` string held by an ID field (`id`, `*_id` or `*Id`) in
+    /// `value` with the aliased ID. Other strings are left alone, so text may start with `@`.
+    pub fn substitute(&self, value: &mut Value) -> Result<()> {
+        self.substitute_in(value, false)
+    }
+
+    fn substitute_in(&self, value: &mut Value, id_field: bool) -> Result<()> {
+        match value {
+            Value::String(s) if id_field => {
+                if let Some(name) = s.strip_prefix('@') {
+                    *value = Value::from(self.get(name)?);
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    self.substitute_in(item, id_field)?;
+                }
+            }
+            Value::Object(fields) => {
+                for (key, field) in fields.iter_mut() {
+                    let id_field = key == "id" || key.ends_with("_id") || key.ends_with("Id");
+                    self.substitute_in(field, id_field)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// Hands out character IDs for new elements.
+///
+/// Knows every ID defined anywhere in the movie (nested sprites included) and every ID
+/// bound by `ImportAssets`. Explicit IDs must be reserved before anything is auto-assigned,
+/// so an auto-assigned ID can never take one a later element asked for.
+#[derive(Debug)]
+pub struct IdAllocator {
+    /// ID → what uses it, for collision errors
+    used: BTreeMap<u16, String>,
+    next: u16,
+    /// Config location of each new element → its ID
+    planned: HashMap<String, u16>,
+    aliases: Aliases,
+    assigned: Vec<AssignedId>,
+}
+
+impl IdAllocator {
+    pub fn new(movie: &Movie) -> Self {
+        let mut used = BTreeMap::new();
+        walk_tags(&movie.tags, &mut |_, tag| {
+            if let Some((id, name)) = character_tag(tag) {
+                used.insert(id, name.to_string());
+            }
+            if let Tag::ImportAssets(import) = tag {
+                for asset in &import.assets {
+                    used.insert(asset.id, format!("import '{}' from '{}'", asset.name, import.url));
+                }
+            }
+        });
+        let next = used.keys().next_back().map_or(1, |&max| max.saturating_add(1));
+        IdAllocator {
+            used,
+            next,
+            planned: HashMap::new(),
+            aliases: Aliases::default(),
+            assigned: Vec::new(),
+        }
+    }
+
+    /// Claims an explicit ID for the element at `location`.
+    pub fn reserve(&mut self, id: u16, location: &str) -> Result<()> {
+        if let Some(existing) = self.used.get(&id) {
+            return Err(StarDeltaError::IdCollision {
+                id,
+                location: location.to_string(),
+                existing: existing.clone(),
+            });
+        }
+        self.used.insert(id, location.to_string());
+        Ok(())
+    }
+
+    /// Gives the element at `location` its ID: `explicit` (which must already be reserved)
+    /// or the next free one, which is recorded for [`IdAllocator::into_assigned`]. `alias` makes
+    /// the ID available as `"@alias"`.
+    pub fn assign(&mut self, explicit: Option<u16>, location: String, tag: &str, alias: Option<&str>) -> Result<u16> {
+        let id = match explicit {
+            Some(id) => id,
+            None => self.next_free(&location)?,
+        };
+        if let Some(alias) = alias {
+            if self.aliases.0.insert(alias.to_string(), id).is_some() {
+                return Err(StarDeltaError::DuplicateAlias {
+                    alias: alias.to_string(),
+                    location,
+                });
+            }
+        }
+        self.planned.insert(location.clone(), id);
+        if explicit.is_none() {
+            self.assigned.push(AssignedId {
+                location,
+                tag: tag.to_string(),
+                id,
+            });
+        }
+        Ok(id)
+    }
+
+    fn next_free(&mut self, location: &str) -> Result<u16> {
+        // ID 0 is the main timeline, so search above the highest ID first, then any gap
+        let id = (self.next..=u16::MAX)
+            .chain(1..self.next)
+            .find(|id| !self.used.contains_key(id))
+            .ok_or_else(|| StarDeltaError::config(format!("{}: no free character IDs left", location)))?;
+        self.used.insert(id, location.to_string());
+        self.next = id.saturating_add(1);
+        Ok(id)
+    }
+
+    /// ID given to the element at `location` by [`IdAllocator::assign`].
+    pub fn planned(&self, location: &str) -> Result<u16> {
+        self.planned
+            .get(location)
+            .copied()
+            .ok_or_else(|| StarDeltaError::config(format!("{}: no ID was assigned", location)))
+    }
+
+    pub fn aliases(&self) -> &Aliases {
+        &self.aliases
+    }
+
+    /// Whether `id` belongs to a new element rather than one already in the movie.
+    pub fn is_new(&self, id: u16) -> bool {
+        self.planned.values().any(|&planned| planned == id)
+    }
+
+    /// IDs picked by [`IdAllocator::assign`] for elements that did not set one.
+    pub fn into_assigned(self) -> Vec<AssignedId> {
+        self.assigned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::{movie, tag};
+
+    fn sprite(id: u16) -> Tag {
+        tag(json!({ "type": "DefineSprite", "id": id, "frame_count": 0, "tags": [] }))
+    }
+
+    fn import(id: u16) -> Tag {
+        tag(json!({ "type": "ImportAssets", "url": "lib.swf", "assets": [{ "id": id, "name": "Star" }] }))
+    }
+
+    #[test]
+    fn assigns_above_nested_and_imported_ids() {
+        let nested = tag(json!({
+            "type": "DefineSprite",
+            "id": 2,
+            "frame_count": 1,
+            "tags": [{ "type": "DefineSprite", "id": 7, "frame_count": 0, "tags": [] }, { "type": "ShowFrame" }]
+        }));
+        let mut ids = IdAllocator::new(&movie(vec![sprite(1), nested, import(5)]));
+        assert_eq!(ids.assign(None, "a".to_string(), "DefineShapeTag", None).unwrap(), 8);
+        assert_eq!(ids.assign(None, "b".to_string(), "DefineShapeTag", None).unwrap(), 9);
+        assert_eq!(ids.planned("a").unwrap(), 8);
+        assert_eq!(ids.into_assigned().len(), 2);
+    }
+
+    #[test]
+    fn fills_gaps_once_the_highest_id_is_taken() {
+        let mut ids = IdAllocator::new(&movie(vec![sprite(1), sprite(3), sprite(u16::MAX)]));
+        assert_eq!(ids.assign(None, "a".to_string(), "DefineShapeTag", None).unwrap(), 2);
+        assert_eq!(ids.assign(None, "b".to_string(), "DefineShapeTag", None).unwrap(), 4);
+    }
+
+    #[test]
+    fn skips_reserved_ids() {
+        let mut ids = IdAllocator::new(&movie(vec![sprite(1)]));
+        ids.reserve(2, "explicit").unwrap();
+        assert_eq!(ids.assign(Some(2), "explicit".to_string(), "DefineShapeTag", None).unwrap(), 2);
+        assert_eq!(ids.assign(None, "auto".to_string(), "DefineShapeTag", None).unwrap(), 3);
+        // Explicit IDs are not reported as assigned
+        assert_eq!(ids.into_assigned().len(), 1);
+    }
+
+    #[test]
+    fn reserving_a_used_id_names_its_owner() {
+        let mut ids = IdAllocator::new(&movie(vec![sprite(1), import(5)]));
+        let existing = |e: StarDeltaError| match e {
+            StarDeltaError::IdCollision { existing, .. } => existing,
+            e => panic!("unexpected error: {}", e),
+        };
+        assert_eq!(existing(ids.reserve(1, "a").unwrap_err()), "DefineSpriteTag");
+        assert_eq!(existing(ids.reserve(5, "a").unwrap_err()), "import 'Star' from 'lib.swf'");
+        ids.reserve(10, "b").unwrap();
+        assert_eq!(existing(ids.reserve(10, "c").unwrap_err()), "b");
+    }
+
+    #[test]
+    fn rejects_duplicate_aliases() {
+        let mut ids = IdAllocator::new(&movie(Vec::new()));
+        ids.assign(None, "a".to_string(), "DefineShapeTag", Some("star")).unwrap();
+        let err = ids.assign(None, "b".to_string(), "DefineShapeTag", Some("star")).unwrap_err();
+        assert!(matches!(err, StarDeltaError::DuplicateAlias { .. }));
+        assert_eq!(ids.aliases().get("star").unwrap(), 1);
+    }
+
+    #[test]
+    fn substitutes_aliases_in_id_fields_only() {
+        let aliases = Aliases(HashMap::from([("star".to_string(), 12)]));
+        let mut value = json!({
+            "id": "@star",
+            "character_id": "@star",
+            "fontId": "@star",
+            "records": [{ "character_id": "@star", "depth": 1 }],
+            "text": "@star",
+            "name": "@star"
+        });
+        aliases.substitute(&mut value).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "id": 12,
+                "character_id": 12,
+                "fontId": 12,
+                "records": [{ "character_id": 12, "depth": 1 }],
+                "text": "@star",
+                "name": "@star"
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_alias_is_an_error() {
+        let mut value = json!({ "character_id": "@missing" });
+        let err = Aliases::default().substitute(&mut value).unwrap_err();
+        assert!(matches!(err, StarDeltaError::UnresolvedTarget { .. }));
+    }
+}
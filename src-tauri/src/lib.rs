@@ -1,12 +1,28 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod swf;
-mod xdelta;
-mod ba2;
+pub mod swf;
+pub mod xdelta;
+pub mod ba2;
+pub mod error;
+pub mod patcher;
+pub mod validate;
+pub mod target;
+pub mod walk;
+pub mod deps;
+pub mod ids;
+pub mod place;
+pub mod edit_text;
+pub mod font;
+pub mod svg;
+#[cfg(test)]
+mod test_util;
 
+#[cfg(feature = "gui")]
 use tauri::Manager;
+#[cfg(feature = "gui")]
 use tauri_plugin_decorum::WebviewWindowExt;
 
+#[cfg(feature = "gui")]
 pub fn run() {
     let builder = tauri::Builder::default();
 
@@ -24,6 +40,8 @@ pub fn run() {
             swf::apply_json_modifications,
             swf::get_file_size,
             swf::batch_process_swf,
+            swf::patch_swf,
+            swf::validate_swf_patch,
             swf::read_file_to_string
         ])
         .plugin(tauri_plugin_decorum::init())
@@ -0,0 +1,340 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use swf_emitter::emit_swf;
+use swf_parser::parse_swf;
+use swf_types::{CompressionMethod, Movie};
+
+use crate::deps::RemovedCharacter;
+use crate::ids::AssignedId;
+use crate::error::{from_json_str, Result, StarDeltaError};
+use crate::swf::{apply_modification_config, ModificationConfig};
+use crate::validate::{validate_config, ValidationReport};
+
+/// Parses a binary SWF into a `Movie`.
+pub fn parse_movie(swf_data: &[u8]) -> Result<Movie> {
+    parse_swf(swf_data).map_err(|e| StarDeltaError::SwfParse { message: e.to_string() })
+}
+
+/// Emits a `Movie` as a binary SWF with the given compression.
+pub fn emit_movie(movie: &Movie, compression: SwfCompression) -> Result<Vec<u8>> {
+    emit_swf(movie, compression.into()).map_err(|e| StarDeltaError::SwfEmit { message: e.to_string() })
+}
+
+/// Body compression of an SWF file, as named in configs (`"none"`, `"zlib"`, `"lzma"`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SwfCompression {
+    /// `FWS` signature
+    #[default]
+    None,
+    /// `CWS` signature
+    Zlib,
+    /// `ZWS` signature
+    Lzma,
+}
+
+impl SwfCompression {
+    /// Reads the compression from the signature of a binary SWF. Unknown signatures are
+    /// treated as uncompressed; `parse_movie` reports them properly.
+    pub fn detect(swf_data: &[u8]) -> Self {
+        match swf_data.get(..3) {
+            Some(b"CWS") => SwfCompression::Zlib,
+            Some(b"ZWS") => SwfCompression::Lzma,
+            _ => SwfCompression::None,
+        }
+    }
+}
+
+impl From<SwfCompression> for CompressionMethod {
+    fn from(compression: SwfCompression) -> Self {
+        match compression {
+            SwfCompression::None => CompressionMethod::None,
+            SwfCompression::Zlib => CompressionMethod::Deflate,
+            SwfCompression::Lzma => CompressionMethod::Lzma,
+        }
+    }
+}
+
+impl fmt::Display for SwfCompression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SwfCompression::None => "none",
+            SwfCompression::Zlib => "zlib",
+            SwfCompression::Lzma => "lzma",
+        })
+    }
+}
+
+impl FromStr for SwfCompression {
+    type Err = StarDeltaError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "none" => Ok(SwfCompression::None),
+            "zlib" => Ok(SwfCompression::Zlib),
+            "lzma" => Ok(SwfCompression::Lzma),
+            _ => Err(StarDeltaError::config(format!(
+                "Unknown compression '{}', expected none, zlib or lzma",
+                s
+            ))),
+        }
+    }
+}
+
+/// How many tags a single `swf.modifications` entry was applied to.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModificationCount {
+    pub location: String,
+    pub tag: String,
+    pub id: u16,
+    pub optional: bool,
+    pub touched: usize,
+}
+
+impl fmt::Display for ModificationCount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} {}): {} tag(s)", self.location, self.tag, self.id, self.touched)?;
+        if self.touched == 0 && self.optional {
+            f.write_str(" (optional)")?;
+        }
+        Ok(())
+    }
+}
+
+/// What applying a config did to a movie.
+#[derive(Debug, Default, Serialize)]
+pub struct PatchSummary {
+    pub modifications: Vec<ModificationCount>,
+    pub removed: Vec<RemovedCharacter>,
+    pub assigned: Vec<AssignedId>,
+}
+
+impl PatchSummary {
+    /// Modifications that touched nothing and are not marked `"optional": true`.
+    pub fn unmatched(&self) -> impl Iterator<Item = &ModificationCount> {
+        self.modifications.iter().filter(|m| m.touched == 0 && !m.optional)
+    }
+}
+
+impl fmt::Display for PatchSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.modifications.is_empty() {
+            writeln!(f, "Modification summary:")?;
+            for count in &self.modifications {
+                writeln!(f, "  {}", count)?;
+            }
+        }
+        for count in self.unmatched() {
+            writeln!(f, "Warning: {} ({} {}) matched no tags", count.location, count.tag, count.id)?;
+        }
+        if !self.removed.is_empty() {
+            writeln!(f, "Removed {} character(s)", self.removed.len())?;
+            for removed in &self.removed {
+                writeln!(f, "  {}", removed)?;
+            }
+        }
+        for assigned in &self.assigned {
+            writeln!(f, "Assigned ID {}", assigned)?;
+        }
+        Ok(())
+    }
+}
+
+/// A modification config ready to be applied to any number of movies.
+///
+/// Relative asset paths in the config (SVG shapes, ActionScript sources) are resolved
+/// against `base_dir`, which is the config file's directory when loaded with
+/// [`Patcher::from_file`].
+#[derive(Debug)]
+pub struct Patcher {
+    config: ModificationConfig,
+    base_dir: PathBuf,
+    strict: bool,
+    default_compression: Option<SwfCompression>,
+}
+
+/// A patched SWF: the emitted file, the movie it was emitted from and what the config
+/// did to it.
+#[derive(Debug)]
+pub struct PatchedSwf {
+    pub data: Vec<u8>,
+    pub movie: Movie,
+    pub summary: PatchSummary,
+}
+
+impl Patcher {
+    pub fn new(config: ModificationConfig, base_dir: impl Into<PathBuf>) -> Self {
+        Patcher {
+            config,
+            base_dir: base_dir.into(),
+            strict: false,
+            default_compression: None,
+        }
+    }
+
+    /// In strict mode, applying fails if any modification not marked `"optional": true`
+    /// matches no tags. Otherwise those are only logged.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Compression for configs that don't set one, instead of keeping the input's, such
+    /// as a batch configuration's `compression`.
+    pub fn default_compression(mut self, compression: Option<SwfCompression>) -> Self {
+        self.default_compression = compression;
+        self
+    }
+
+    /// Loads a modification config from disk.
+    pub fn from_file(config_path: impl AsRef<Path>) -> Result<Self> {
+        let config_path = config_path.as_ref();
+        let config_json = fs::read_to_string(config_path)
+            .map_err(|e| StarDeltaError::io("Failed to read config JSON file", config_path, e))?;
+        let base_dir = config_path
+            .parent()
+            .ok_or_else(|| StarDeltaError::config("Could not determine config file directory"))?;
+        let config: ModificationConfig = from_json_str(&config_json, &config_path.display().to_string())?;
+        Ok(Self::new(config, base_dir))
+    }
+
+    /// Parses a modification config from a JSON string.
+    pub fn from_json(config_json: &str, base_dir: impl Into<PathBuf>) -> Result<Self> {
+        let config: ModificationConfig = from_json_str(config_json, "<config>")?;
+        Ok(Self::new(config, base_dir))
+    }
+
+    pub fn config(&self) -> &ModificationConfig {
+        &self.config
+    }
+
+    pub fn base_dir(&self) -> &Path {
+        &self.base_dir
+    }
+
+    /// Applies the config to a parsed movie in place.
+    pub fn apply(&self, movie: &mut Movie) -> Result<PatchSummary> {
+        let summary = apply_modification_config(movie, &self.config, &self.base_dir)?;
+        let unmatched: Vec<String> = summary.unmatched().map(|m| m.location.clone()).collect();
+        if self.strict && !unmatched.is_empty() {
+            return Err(StarDeltaError::UnmatchedModifications { locations: unmatched });
+        }
+        Ok(summary)
+    }
+
+    /// Resolves the config against `movie` without modifying it.
+    pub fn validate(&self, movie: &Movie) -> ValidationReport {
+        validate_config(movie, &self.config)
+    }
+
+    /// Compression to emit with: the config's override if set, then the default
+    /// compression, otherwise `original`.
+    pub fn compression(&self, original: SwfCompression) -> SwfCompression {
+        self.config.compression.or(self.default_compression).unwrap_or(original)
+    }
+
+    /// Parses `swf_data`, applies the config and emits the patched SWF, keeping the
+    /// input's compression unless overridden (see [`Patcher::compression`]).
+    pub fn patch_bytes(&self, swf_data: &[u8]) -> Result<PatchedSwf> {
+        let mut movie = parse_movie(swf_data)?;
+        let summary = self.apply(&mut movie)?;
+        let data = emit_movie(&movie, self.compression(SwfCompression::detect(swf_data)))?;
+        Ok(PatchedSwf { data, movie, summary })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::{movie, tag_list};
+    use crate::validate::ValidationIssue;
+
+    fn swf(compression: SwfCompression) -> Vec<u8> {
+        emit_movie(&movie(tag_list(json!([{ "type": "ShowFrame" }]))), compression).unwrap()
+    }
+
+    #[test]
+    fn compression_is_read_from_the_signature_and_parsed_by_name() {
+        assert_eq!(SwfCompression::detect(b"FWS\x0a"), SwfCompression::None);
+        assert_eq!(SwfCompression::detect(b"CWS\x0a"), SwfCompression::Zlib);
+        assert_eq!(SwfCompression::detect(b"ZWS\x0d"), SwfCompression::Lzma);
+        assert_eq!(SwfCompression::detect(b""), SwfCompression::None);
+        for compression in [SwfCompression::None, SwfCompression::Zlib, SwfCompression::Lzma] {
+            assert_eq!(compression.to_string().parse::<SwfCompression>().unwrap(), compression);
+        }
+        assert_eq!("LZMA".parse::<SwfCompression>().unwrap(), SwfCompression::Lzma);
+        assert!("gzip".parse::<SwfCompression>().is_err());
+    }
+
+    #[test]
+    fn patch_bytes_keeps_the_input_compression_unless_overridden() {
+        let keep = Patcher::from_json(r#"{ "swf": { "modifications": [] } }"#, ".").unwrap();
+        let patched = keep.patch_bytes(&swf(SwfCompression::Zlib)).unwrap();
+        assert_eq!(SwfCompression::detect(&patched.data), SwfCompression::Zlib);
+
+        let uncompressed = Patcher::from_json(r#"{ "swf": { "modifications": [] }, "compression": "none" }"#, ".").unwrap();
+        let patched = uncompressed.patch_bytes(&swf(SwfCompression::Zlib)).unwrap();
+        assert_eq!(SwfCompression::detect(&patched.data), SwfCompression::None);
+        assert_eq!(parse_movie(&patched.data).unwrap().tags.len(), 1);
+    }
+
+    #[test]
+    fn config_compression_wins_over_the_default() {
+        let batch = |config: &str| {
+            let patcher = Patcher::from_json(config, ".").unwrap().default_compression(Some(SwfCompression::Lzma));
+            SwfCompression::detect(&patcher.patch_bytes(&swf(SwfCompression::Zlib)).unwrap().data)
+        };
+        assert_eq!(batch(r#"{ "swf": { "modifications": [] } }"#), SwfCompression::Lzma);
+        assert_eq!(batch(r#"{ "swf": { "modifications": [] }, "compression": "none" }"#), SwfCompression::None);
+    }
+
+    #[test]
+    fn strict_mode_fails_on_unmatched_modifications() {
+        let config = json!({ "swf": { "modifications": [
+            { "tag": "DefineSpriteTag", "id": 9, "properties": { "tags": [] } },
+            { "tag": "DefineSpriteTag", "id": 8, "properties": { "tags": [] }, "optional": true }
+        ] } })
+        .to_string();
+        let mut movie = movie(Vec::new());
+
+        let summary = Patcher::from_json(&config, ".").unwrap().apply(&mut movie).unwrap();
+        let unmatched: Vec<_> = summary.unmatched().map(|m| m.location.as_str()).collect();
+        assert_eq!(unmatched, ["swf.modifications[0]"]);
+
+        let err = Patcher::from_json(&config, ".").unwrap().strict(true).apply(&mut movie).unwrap_err();
+        assert!(matches!(
+            err,
+            StarDeltaError::UnmatchedModifications { ref locations } if locations == &["swf.modifications[0]"]
+        ));
+    }
+
+    #[test]
+    fn validation_sees_the_movie_as_json_patch_leaves_it() {
+        let report = |patch: serde_json::Value| {
+            let config = json!({
+                "json_patch": patch,
+                "swf": { "modifications": [] },
+                "remove_elements": { "sprites": [5] }
+            });
+            let movie = movie(tag_list(json!([{ "type": "ShowFrame" }])));
+            Patcher::from_json(&config.to_string(), ".").unwrap().validate(&movie)
+        };
+
+        let sprite = json!({ "type": "DefineSprite", "id": 5, "frame_count": 0, "tags": [] });
+        assert!(report(json!([{ "op": "add", "path": "/tags/0", "value": sprite }])).is_clean());
+
+        let issues = report(json!([{ "op": "replace", "path": "/tags/0/type", "value": "Sprite" }])).issues;
+        assert!(matches!(
+            &issues[0],
+            ValidationIssue::JsonPatchFailed { location, .. } if location == "json_patch"
+        ));
+        assert!(matches!(&issues[1], ValidationIssue::UnmatchedId { id: 5, .. }));
+    }
+}
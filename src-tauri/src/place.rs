@@ -0,0 +1,264 @@
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use swf_types::{tags, Tag};
+
+use crate::deps::references;
+use crate::error::{Result, StarDeltaError};
+use crate::swf::{character_tag, tag_config_name};
+
+/// An explicit spot on the root timeline for a new tag, written
+/// `"before": {"tag": "ShowFrame", "index": 0}`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Anchor {
+    pub tag: String,   // Tag type, with or without the `Tag` suffix
+    #[serde(default)]
+    pub index: usize,  // Which tag of that type, 0-based
+}
+
+impl Anchor {
+    /// Index of the anchor tag in `tags`.
+    pub fn position(&self, tags: &[Tag]) -> Result<usize> {
+        let name = self.tag.strip_suffix("Tag").unwrap_or(&self.tag);
+        tags.iter()
+            .enumerate()
+            .filter(|(_, tag)| tag_config_name(tag).is_some_and(|n| n.strip_suffix("Tag") == Some(name)))
+            .nth(self.index)
+            .map(|(i, _)| i)
+            .ok_or_else(|| {
+                StarDeltaError::config(format!("no {}Tag with index {} on the root timeline", name, self.index))
+            })
+    }
+}
+
+/// Index of the `ShowFrame` that ends `frame` (0-based), where tags for that frame go.
+pub fn frame_end(timeline: &[Tag], frame: usize) -> Option<usize> {
+    timeline
+        .iter()
+        .enumerate()
+        .filter(|(_, tag)| matches!(tag, Tag::ShowFrame { .. }))
+        .nth(frame)
+        .map(|(i, _)| i)
+}
+
+/// Default spot for a new root-level tag: in front of the first `ShowFrame`, so it is loaded
+/// with frame 0 even in files whose tag list ends without a final `ShowFrame`.
+fn first_frame_end(tags: &[Tag]) -> usize {
+    frame_end(tags, 0).unwrap_or(tags.len())
+}
+
+/// Inserts a new definition (or other frame 0 tag) at `anchor`, or in front of the first
+/// `ShowFrame` without one.
+pub fn insert_definition(tags: &mut Vec<Tag>, tag: Tag, anchor: Option<&Anchor>) -> Result<()> {
+    let index = match anchor {
+        Some(anchor) => anchor.position(tags)?,
+        None => first_frame_end(tags),
+    };
+    tags.insert(index, tag);
+    Ok(())
+}
+
+/// Inserts a new `DoAbc` at `anchor` or, without one, in front of the first `SymbolClass`
+/// and the first `ShowFrame`, so its classes exist before symbols are bound to them.
+pub fn insert_do_abc(tags: &mut Vec<Tag>, tag: Tag, anchor: Option<&Anchor>) -> Result<()> {
+    let index = match anchor {
+        Some(anchor) => anchor.position(tags)?,
+        None => {
+            let frame_end = first_frame_end(tags);
+            let symbol_class = tags.iter().position(|t| matches!(t, Tag::SymbolClass(_)));
+            symbol_class.map_or(frame_end, |i| i.min(frame_end))
+        }
+    };
+    tags.insert(index, tag);
+    Ok(())
+}
+
+/// Inserts a new `ImportAssets` at `anchor` or, without one, after the last `ImportAssets`,
+/// or else in front of the first tag that defines or uses a character, so imported IDs are
+/// bound before anything refers to them.
+pub fn insert_import(tags: &mut Vec<Tag>, tag: Tag, anchor: Option<&Anchor>) -> Result<()> {
+    let index = match anchor {
+        Some(anchor) => anchor.position(tags)?,
+        None => match tags.iter().rposition(|t| matches!(t, Tag::ImportAssets(_))) {
+            Some(last) => last + 1,
+            None => {
+                let frame_end = first_frame_end(tags);
+                let first_character = tags.iter().position(|t| character_tag(t).is_some() || !references(t).is_empty());
+                first_character.map_or(frame_end, |i| i.min(frame_end))
+            }
+        },
+    };
+    tags.insert(index, tag);
+    Ok(())
+}
+
+/// Depths holding a character after every tag in `timeline` has run.
+fn occupied_depths(timeline: &[Tag]) -> BTreeSet<u16> {
+    let mut depths = BTreeSet::new();
+    for tag in timeline {
+        match tag {
+            Tag::PlaceObject(place) if place.character_id.is_some() => {
+                depths.insert(place.depth);
+            }
+            Tag::RemoveObject(remove) => {
+                depths.remove(&remove.depth);
+            }
+            _ => {}
+        }
+    }
+    depths
+}
+
+/// Adds `place` at the end of `frame` of `timeline`. The depth must be free at that point,
+/// since a new placement cannot replace a character already on the display list.
+pub fn insert_place_object(timeline: &mut Vec<Tag>, frame: usize, place: tags::PlaceObject) -> Result<()> {
+    let index = frame_end(timeline, frame)
+        .ok_or_else(|| StarDeltaError::config(format!("the timeline has no frame {}", frame)))?;
+    if occupied_depths(&timeline[..index]).contains(&place.depth) {
+        return Err(StarDeltaError::config(format!(
+            "depth {} is already used at frame {}",
+            place.depth, frame
+        )));
+    }
+    timeline.insert(index, Tag::PlaceObject(place));
+    Ok(())
+}
+
+/// Moves each root-level definition of `ids` in front of the first tag that refers to it,
+/// repeating until nothing moves so definitions used by other moved definitions follow them.
+/// An acyclic set settles within `ids.len()` passes; definitions that refer to each other
+/// never do and are an error.
+pub fn move_before_first_use(tags: &mut Vec<Tag>, ids: &[u16]) -> Result<()> {
+    for _ in 0..=ids.len() {
+        let mut moved = false;
+        for &id in ids {
+            let Some(definition) = tags.iter().position(|t| character_tag(t).is_some_and(|(i, _)| i == id)) else {
+                continue;
+            };
+            if let Some(first_use) = tags[..definition].iter().position(|t| references(t).contains(&id)) {
+                let tag = tags.remove(definition);
+                tags.insert(first_use, tag);
+                moved = true;
+            }
+        }
+        if !moved {
+            return Ok(());
+        }
+    }
+    Err(StarDeltaError::config(format!(
+        "new definitions {:?} refer to each other, so none of them can be defined first",
+        ids
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::{tag, tag_list};
+
+    fn place(depth: u16, character_id: u16) -> tags::PlaceObject {
+        serde_json::from_value(json!({ "is_update": false, "depth": depth, "character_id": character_id })).unwrap()
+    }
+
+    #[test]
+    fn place_object_needs_a_free_depth() {
+        let mut timeline = tag_list(json!([
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 2 },
+            { "type": "ShowFrame" },
+            { "type": "RemoveObject", "depth": 1 },
+            { "type": "ShowFrame" }
+        ]));
+        assert!(insert_place_object(&mut timeline, 0, place(1, 3)).is_err());
+        assert!(insert_place_object(&mut timeline, 2, place(2, 3)).is_err());
+        assert_eq!(timeline.len(), 4);
+
+        insert_place_object(&mut timeline, 0, place(2, 3)).unwrap();
+        assert!(matches!(&timeline[1], Tag::PlaceObject(p) if p.depth == 2));
+        insert_place_object(&mut timeline, 1, place(1, 3)).unwrap();
+        assert!(matches!(&timeline[4], Tag::PlaceObject(p) if p.depth == 1 && p.character_id == Some(3)));
+    }
+
+    #[test]
+    fn definitions_move_in_front_of_their_first_use() {
+        let mut timeline = tag_list(json!([
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 2 },
+            { "type": "ShowFrame" },
+            { "type": "DefineSprite", "id": 2, "frame_count": 1, "tags": [
+                { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 3 },
+                { "type": "ShowFrame" }
+            ] },
+            { "type": "DefineSprite", "id": 3, "frame_count": 0, "tags": [] }
+        ]));
+        move_before_first_use(&mut timeline, &[2, 3]).unwrap();
+        let order: Vec<_> = timeline.iter().map(|t| character_tag(t).map(|(id, _)| id)).collect();
+        assert_eq!(order, [Some(3), Some(2), None, None]);
+    }
+
+    fn anchor(tag: &str, index: usize) -> Anchor {
+        Anchor {
+            tag: tag.to_string(),
+            index,
+        }
+    }
+
+    fn do_abc() -> Tag {
+        Tag::DoAbc(tags::DoAbc {
+            header: None,
+            data: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn anchor_counts_tags_of_its_type() {
+        let timeline = tag_list(json!([
+            { "type": "DefineSprite", "id": 1, "frame_count": 0, "tags": [] },
+            { "type": "ShowFrame" },
+            { "type": "DefineSprite", "id": 2, "frame_count": 0, "tags": [] },
+            { "type": "ShowFrame" }
+        ]));
+        assert_eq!(anchor("ShowFrame", 0).position(&timeline).unwrap(), 1);
+        assert_eq!(anchor("ShowFrameTag", 1).position(&timeline).unwrap(), 3);
+        assert_eq!(anchor("DefineSpriteTag", 1).position(&timeline).unwrap(), 2);
+        assert!(anchor("ShowFrame", 2).position(&timeline).is_err());
+        assert!(anchor("SymbolClass", 0).position(&timeline).is_err());
+    }
+
+    #[test]
+    fn do_abc_goes_before_symbol_class_and_first_frame() {
+        let mut timeline = tag_list(json!([
+            { "type": "DefineSprite", "id": 1, "frame_count": 0, "tags": [] },
+            { "type": "SymbolClass", "symbols": [{ "id": 1, "name": "Star" }] },
+            { "type": "ShowFrame" }
+        ]));
+        insert_do_abc(&mut timeline, do_abc(), None).unwrap();
+        assert!(matches!(timeline[1], Tag::DoAbc(_)));
+
+        let mut timeline = tag_list(json!([
+            { "type": "DefineSprite", "id": 1, "frame_count": 0, "tags": [] },
+            { "type": "ShowFrame" },
+            { "type": "SymbolClass", "symbols": [{ "id": 1, "name": "Star" }] }
+        ]));
+        insert_do_abc(&mut timeline, do_abc(), None).unwrap();
+        assert!(matches!(timeline[1], Tag::DoAbc(_)));
+
+        insert_do_abc(&mut timeline, do_abc(), Some(&anchor("SymbolClass", 0))).unwrap();
+        assert!(matches!(timeline[3], Tag::DoAbc(_)));
+        assert!(matches!(timeline[4], Tag::SymbolClass(_)));
+    }
+
+    #[test]
+    fn definitions_that_place_each_other_are_an_error() {
+        let mut timeline = vec![
+            tag(json!({ "type": "DefineSprite", "id": 2, "frame_count": 1, "tags": [
+                { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 3 },
+                { "type": "ShowFrame" }
+            ] })),
+            tag(json!({ "type": "DefineSprite", "id": 3, "frame_count": 1, "tags": [
+                { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 2 },
+                { "type": "ShowFrame" }
+            ] })),
+        ];
+        assert!(move_before_first_use(&mut timeline, &[2, 3]).is_err());
+    }
+}
@@ -0,0 +1,664 @@
+use kurbo::CubicBez;
+use std::fs;
+use std::path::{Path, PathBuf};
+use swf_fixed::{Sfixed16P16, Sfixed8P8};
+use swf_types::{
+    fill_styles, shape_records, CapStyle, ColorSpace, ColorStop, FillStyle, Gradient, GradientSpread, JoinStyle,
+    LineStyle, Matrix, Shape, ShapeRecord, ShapeStyles, StraightSRgba8, Vector2D,
+};
+use usvg::tiny_skia_path::{PathSegment, Point};
+use usvg::{BaseGradient, FillRule, Group, LineCap, LineJoin, Node, Paint, SpreadMethod, Stop, Transform, Tree};
+
+use crate::error::{Result, StarDeltaError};
+
+const SWF_SCALE: f32 = 20.0;  // SWF uses 20 twips per pixel, whereas SVG uses 1px per pixel
+/// SWF gradients are drawn on a square from -16384 to 16384 twips, mapped by their matrix.
+const GRADIENT_SQUARE: f32 = 32768.0;
+/// Most color stops a `DefineShape4` gradient can hold.
+const MAX_GRADIENT_STOPS: usize = 15;
+/// Default largest distance, in twips, between an SVG curve and the quadratic curves drawing it.
+pub const DEFAULT_CURVE_TOLERANCE: f64 = 1.0;
+
+/// A shape read from an SVG file.
+pub struct SvgShape {
+    pub shape: Shape,
+    pub fill_winding: bool,  // Fills use the nonzero rule, which needs `DefineShape4`
+}
+
+/// An SVG file, normalized by `usvg` so basic shapes, `<use>`, CSS styles, inherited fill
+/// and stroke, nested transforms, `viewBox` and units all arrive as paths in pixels.
+pub struct SvgFile {
+    path: PathBuf,
+    tree: Tree,
+}
+
+impl SvgFile {
+    pub fn load(path: &Path) -> Result<Self> {
+        println!("Starting to parse SVG file: {}", path.display());
+        let svg_data = fs::read(path).map_err(|e| StarDeltaError::io("Failed to read SVG file", path, e))?;
+        let options = usvg::Options {
+            resources_dir: path.parent().map(Path::to_path_buf),
+            ..Default::default()
+        };
+        let tree = Tree::from_data(&svg_data, &options).map_err(|e| StarDeltaError::svg(path, e))?;
+        Ok(SvgFile {
+            path: path.to_path_buf(),
+            tree,
+        })
+    }
+
+    /// Converts the whole drawing, or only the element with ID `svg_id` (a `<g>` layer, a
+    /// `<use>` or a single shape), to a shape. Elements keep their place in the drawing, so
+    /// layers stacked on top of each other line up. Each SVG path gets its own style set, so
+    /// later paths are drawn over earlier ones. Cubic curves, which include arcs, are split
+    /// into quadratic curves until they are within `tolerance` twips of the original.
+    pub fn shape(&self, svg_id: Option<&str>, tolerance: Option<f64>) -> Result<SvgShape> {
+        let tolerance = tolerance.unwrap_or(DEFAULT_CURVE_TOLERANCE);
+        if !(tolerance.is_finite() && tolerance > 0.0) {
+            return Err(StarDeltaError::config(format!(
+                "curve tolerance for '{}' must be a positive number of twips, got {}",
+                self.path.display(),
+                tolerance
+            )));
+        }
+
+        let mut builder = ShapeBuilder {
+            tolerance,
+            ..Default::default()
+        };
+        match svg_id {
+            Some(id) => {
+                let (node, opacity) = find_node(self.tree.root(), id, 1.0)
+                    .ok_or_else(|| StarDeltaError::svg(&self.path, format!("no element with id '{}'", id)))?;
+                builder.add_node(node, opacity);
+            }
+            None => builder.add_group(self.tree.root(), 1.0),
+        }
+        println!("Converted {} SVG paths", builder.path_count);
+        if builder.records.is_empty() {
+            let element = svg_id.map_or("SVG file".to_string(), |id| format!("element '{}'", id));
+            return Err(StarDeltaError::svg(&self.path, format!("No shapes found in {}", element)));
+        }
+
+        // The fill rule is set for the whole shape, so even-odd paths with holes keep it even-odd
+        let (nonzero, even_odd) = builder.overlapping_fills;
+        if nonzero && even_odd {
+            println!("Warning: SVG mixes nonzero and even-odd fill rules, filling every path even-odd");
+        }
+        Ok(SvgShape {
+            shape: Shape {
+                initial_styles: ShapeStyles {
+                    fill: Vec::new(),
+                    line: Vec::new(),
+                },
+                records: builder.records,
+            },
+            fill_winding: nonzero && !even_odd,
+        })
+    }
+}
+
+/// Finds the node with ID `id` below `group`, with the opacity of the groups around it.
+fn find_node<'a>(group: &'a Group, id: &str, opacity: f32) -> Option<(&'a Node, f32)> {
+    let opacity = opacity * group.opacity().get();
+    group.children().iter().find_map(|node| match node {
+        _ if node.id() == id => Some((node, opacity)),
+        Node::Group(child) => find_node(child, id, opacity),
+        _ => None,
+    })
+}
+
+fn opacity_to_alpha(opacity: f32) -> u8 {
+    (opacity.clamp(0.0, 1.0) * 255.0) as u8
+}
+
+/// SWF fill for an SVG paint on a path drawn with `transform`, for fills and strokes alike.
+fn paint_fill(paint: &Paint, opacity: f32, transform: Transform) -> Option<FillStyle> {
+    match paint {
+        Paint::Color(color) => Some(FillStyle::Solid(fill_styles::Solid {
+            color: StraightSRgba8 {
+                r: color.red,
+                g: color.green,
+                b: color.blue,
+                a: opacity_to_alpha(opacity),
+            },
+        })),
+        Paint::LinearGradient(linear) => {
+            // The square's x axis runs from (x1, y1) to (x2, y2), its y axis at a right angle
+            let (dx, dy) = (linear.x2() - linear.x1(), linear.y2() - linear.y1());
+            let square = Transform::from_row(
+                dx / GRADIENT_SQUARE,
+                dy / GRADIENT_SQUARE,
+                -dy / GRADIENT_SQUARE,
+                dx / GRADIENT_SQUARE,
+                (linear.x1() + linear.x2()) / 2.0,
+                (linear.y1() + linear.y2()) / 2.0,
+            );
+            Some(FillStyle::LinearGradient(fill_styles::LinearGradient {
+                matrix: gradient_matrix(transform, linear, square),
+                gradient: gradient(linear, opacity),
+            }))
+        }
+        Paint::RadialGradient(radial) => {
+            // The square's circle becomes the `r` circle, its x axis turned toward the focal point
+            let (cx, cy, r) = (radial.cx(), radial.cy(), radial.r().get());
+            let (fx, fy) = (radial.fx() - cx, radial.fy() - cy);
+            let focal = (fx * fx + fy * fy).sqrt() / r;
+            let scale = 2.0 * r / GRADIENT_SQUARE;
+            let square = Transform::from_translate(cx, cy)
+                .pre_concat(Transform::from_rotate(fy.atan2(fx).to_degrees()))
+                .pre_concat(Transform::from_scale(scale, scale));
+            let matrix = gradient_matrix(transform, radial, square);
+            let gradient = gradient(radial, opacity);
+            if focal < 0.005 {
+                return Some(FillStyle::RadialGradient(fill_styles::RadialGradient { matrix, gradient }));
+            }
+            Some(FillStyle::FocalGradient(fill_styles::FocalGradient {
+                matrix,
+                gradient,
+                // A focal point on the circle itself can't be drawn, so keep it just inside
+                focal_point: Sfixed8P8::from_epsilons((focal.min(0.99) * 256.0).round() as i16),
+            }))
+        }
+        Paint::Pattern(_) => {
+            println!("Warning: SVG patterns are not supported, skipping the paint");
+            None
+        }
+    }
+}
+
+/// Matrix from the gradient square to the shape, in twips: the square mapped to the
+/// gradient's own space, then `gradientTransform`, then the path's transform.
+fn gradient_matrix(transform: Transform, gradient: &BaseGradient, square: Transform) -> Matrix {
+    let ts = Transform::from_scale(SWF_SCALE, SWF_SCALE)
+        .pre_concat(transform)
+        .pre_concat(gradient.transform())
+        .pre_concat(square);
+    let fixed = |value: f32| Sfixed16P16::from_epsilons((f64::from(value) * 65536.0).round() as i32);
+    Matrix {
+        scale_x: fixed(ts.sx),
+        scale_y: fixed(ts.sy),
+        rotate_skew0: fixed(ts.ky),
+        rotate_skew1: fixed(ts.kx),
+        translate_x: ts.tx.round() as i32,
+        translate_y: ts.ty.round() as i32,
+    }
+}
+
+fn gradient(gradient: &BaseGradient, opacity: f32) -> Gradient {
+    let stop_color = |color: usvg::Color, stop_opacity: f32| StraightSRgba8 {
+        r: color.red,
+        g: color.green,
+        b: color.blue,
+        a: opacity_to_alpha(stop_opacity * opacity),
+    };
+    let stops = gradient.stops();
+    let colors = if stops.len() <= MAX_GRADIENT_STOPS {
+        stops
+            .iter()
+            .map(|stop| ColorStop {
+                ratio: (stop.offset().get() * 255.0).round() as u8,
+                color: stop_color(stop.color(), stop.opacity().get()),
+            })
+            .collect()
+    } else {
+        // Too many stops: sample the gradient at evenly spaced offsets instead
+        println!("Warning: gradient '{}' has {} stops, resampled to {}", gradient.id(), stops.len(), MAX_GRADIENT_STOPS);
+        (0..MAX_GRADIENT_STOPS)
+            .map(|i| {
+                let offset = i as f32 / (MAX_GRADIENT_STOPS - 1) as f32;
+                let (color, stop_opacity) = sample_stops(stops, offset);
+                ColorStop {
+                    ratio: (offset * 255.0).round() as u8,
+                    color: stop_color(color, stop_opacity),
+                }
+            })
+            .collect()
+    };
+    Gradient {
+        spread: match gradient.spread_method() {
+            SpreadMethod::Pad => GradientSpread::Pad,
+            SpreadMethod::Reflect => GradientSpread::Reflect,
+            SpreadMethod::Repeat => GradientSpread::Repeat,
+        },
+        color_space: ColorSpace::SRgb,
+        colors,
+    }
+}
+
+/// Color and opacity of a gradient at `offset`, between the stops around it.
+fn sample_stops(stops: &[Stop], offset: f32) -> (usvg::Color, f32) {
+    let after = stops.iter().position(|s| s.offset().get() >= offset).unwrap_or(stops.len() - 1);
+    let (from, to) = (&stops[after.saturating_sub(1)], &stops[after]);
+    let span = to.offset().get() - from.offset().get();
+    let t = if span > 0.0 { (offset - from.offset().get()) / span } else { 1.0 };
+    let mix = |a: u8, b: u8| (f32::from(a) + (f32::from(b) - f32::from(a)) * t).round() as u8;
+    let (a, b) = (from.color(), to.color());
+    let color = usvg::Color::new_rgb(mix(a.red, b.red), mix(a.green, b.green), mix(a.blue, b.blue));
+    let opacity = from.opacity().get() + (to.opacity().get() - from.opacity().get()) * t;
+    (color, opacity)
+}
+
+/// Whether a shape uses styles only `DefineShape4` can hold: focal gradients, gradients
+/// with more than 8 stops, or strokes filled with anything but a color.
+pub fn needs_define_shape4(shape: &Shape) -> bool {
+    let fill_needs_shape4 = |fill: &FillStyle| match fill {
+        FillStyle::FocalGradient(_) => true,
+        FillStyle::LinearGradient(f) => f.gradient.colors.len() > 8,
+        FillStyle::RadialGradient(f) => f.gradient.colors.len() > 8,
+        _ => false,
+    };
+    let styles_need_shape4 = |styles: &ShapeStyles| {
+        styles.fill.iter().any(fill_needs_shape4)
+            || styles.line.iter().any(|line| !matches!(line.fill, FillStyle::Solid(_)))
+    };
+    styles_need_shape4(&shape.initial_styles)
+        || shape.records.iter().any(|record| match record {
+            ShapeRecord::StyleChange(change) => change.new_styles.as_ref().is_some_and(styles_need_shape4),
+            _ => false,
+        })
+}
+
+fn cap_style(cap: LineCap) -> CapStyle {
+    match cap {
+        LineCap::Butt => CapStyle::None,
+        LineCap::Round => CapStyle::Round,
+        LineCap::Square => CapStyle::Square,
+    }
+}
+
+fn join_style(join: LineJoin) -> JoinStyle {
+    match join {
+        LineJoin::Bevel => JoinStyle::Bevel,
+        // Miter joins need a fixed-point miter limit, so they are drawn round like before
+        LineJoin::Round | LineJoin::Miter | LineJoin::MiterClip => JoinStyle::Round,
+    }
+}
+
+/// Collects shape records for the paths of a `usvg` tree, in twips.
+#[derive(Default)]
+struct ShapeBuilder {
+    records: Vec<ShapeRecord>,
+    pen: (i32, i32),
+    last: kurbo::Point,  // Pen position before rounding, where the next cubic starts
+    tolerance: f64,
+    path_count: usize,
+    overlapping_fills: (bool, bool),  // Some filled path with several contours is (nonzero, even-odd)
+}
+
+impl ShapeBuilder {
+    /// Adds the paths of `group`, drawn at `opacity`: the product of the opacities of the
+    /// groups around it. Transforms, fill and stroke are already resolved on each path.
+    fn add_group(&mut self, group: &Group, opacity: f32) {
+        let opacity = opacity * group.opacity().get();
+        if group.clip_path().is_some() || group.mask().is_some() || !group.filters().is_empty() {
+            println!("Warning: SVG clip paths, masks and filters are not supported, ignoring them on '{}'", group.id());
+        }
+        for node in group.children() {
+            self.add_node(node, opacity);
+        }
+    }
+
+    fn add_node(&mut self, node: &Node, opacity: f32) {
+        match node {
+            Node::Group(group) => self.add_group(group, opacity),
+            Node::Path(path) if path.is_visible() => self.add_path(path, opacity),
+            Node::Path(_) => {}
+            Node::Text(text) => {
+                if text.flattened().children().is_empty() {
+                    println!("Warning: SVG text '{}' has no outlines, convert it to paths", text.id());
+                }
+                self.add_group(text.flattened(), opacity);
+            }
+            Node::Image(image) => {
+                println!("Warning: SVG images are not supported, skipping '{}'", image.id());
+            }
+        }
+    }
+
+    fn add_path(&mut self, path: &usvg::Path, opacity: f32) {
+        let transform = path.abs_transform();
+        let Some(data) = path.data().clone().transform(transform) else {
+            return;
+        };
+        let fill = path.fill().and_then(|f| paint_fill(f.paint(), f.opacity().get() * opacity, transform));
+        let line = path.stroke().and_then(|stroke| {
+            let fill = paint_fill(stroke.paint(), stroke.opacity().get() * opacity, transform)?;
+            // Strokes scale with the path, like the geometry the transform was applied to
+            let scale = transform.get_scale();
+            let width = stroke.width().get() * (scale.0 + scale.1) / 2.0;
+            Some(LineStyle {
+                width: (width * SWF_SCALE).round() as u16,
+                start_cap: cap_style(stroke.linecap()),
+                end_cap: cap_style(stroke.linecap()),
+                join: join_style(stroke.linejoin()),
+                no_h_scale: false,
+                no_v_scale: false,
+                no_close: false,
+                pixel_hinting: false,
+                fill,
+            })
+        });
+        if fill.is_none() && line.is_none() {
+            return;
+        }
+        self.path_count += 1;
+
+        // The SWF default is even-odd; nonzero only differs once contours overlap
+        let contours = data.segments().filter(|s| matches!(s, PathSegment::MoveTo(_))).count();
+        if let Some(rule) = path.fill().filter(|_| fill.is_some() && contours > 1).map(usvg::Fill::rule) {
+            match rule {
+                FillRule::NonZero => self.overlapping_fills.0 = true,
+                FillRule::EvenOdd => self.overlapping_fills.1 = true,
+            }
+        }
+
+        // Every path starts a new style set holding just its own fill and stroke
+        self.records.push(ShapeRecord::StyleChange(shape_records::StyleChange {
+            move_to: None,
+            left_fill: fill.is_some().then_some(1),
+            right_fill: None,
+            line_style: line.is_some().then_some(1),
+            new_styles: Some(ShapeStyles {
+                fill: fill.into_iter().collect(),
+                line: line.into_iter().collect(),
+            }),
+        }));
+
+        let mut start = self.last;
+        for segment in data.segments() {
+            match segment {
+                PathSegment::MoveTo(p) => {
+                    let to = twips(p);
+                    let (x, y) = round(to);
+                    self.records.push(ShapeRecord::StyleChange(shape_records::StyleChange {
+                        move_to: Some(Vector2D { x, y }),
+                        left_fill: None,
+                        right_fill: None,
+                        line_style: None,
+                        new_styles: None,
+                    }));
+                    self.pen = (x, y);
+                    self.last = to;
+                    start = to;
+                }
+                PathSegment::LineTo(p) => self.edge(None, twips(p)),
+                PathSegment::QuadTo(control, p) => self.edge(Some(twips(control)), twips(p)),
+                PathSegment::CubicTo(control1, control2, p) => {
+                    let cubic = CubicBez::new(self.last, twips(control1), twips(control2), twips(p));
+                    for (_, _, quad) in cubic.to_quads(self.tolerance) {
+                        self.edge(Some(quad.p1), quad.p2);
+                    }
+                }
+                PathSegment::Close => {
+                    if self.pen != round(start) {
+                        self.edge(None, start);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adds a straight or quadratic edge from the pen to `to`, both in twips.
+    fn edge(&mut self, control: Option<kurbo::Point>, to: kurbo::Point) {
+        let delta = |(x, y): (i32, i32)| Vector2D {
+            x: x - self.pen.0,
+            y: y - self.pen.1,
+        };
+        let end = round(to);
+        self.records.push(ShapeRecord::Edge(shape_records::Edge {
+            delta: delta(end),
+            control_delta: control.map(|c| delta(round(c))),
+        }));
+        self.pen = end;
+        self.last = to;
+    }
+}
+
+/// Unrounded position in twips.
+fn twips(p: Point) -> kurbo::Point {
+    kurbo::Point::new(f64::from(p.x * SWF_SCALE), f64::from(p.y * SWF_SCALE))
+}
+
+/// Absolute position in whole twips; edges are rounded from these so errors don't add up.
+fn round(p: kurbo::Point) -> (i32, i32) {
+    (p.x.round() as i32, p.y.round() as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `body` drawn on a 100x100 px canvas.
+    fn svg(body: &str) -> SvgFile {
+        let data = format!(r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="100">{}</svg>"#, body);
+        SvgFile {
+            path: PathBuf::from("test.svg"),
+            tree: Tree::from_str(&data, &usvg::Options::default()).unwrap(),
+        }
+    }
+
+    fn svg_shape_with(body: &str, tolerance: Option<f64>) -> Result<SvgShape> {
+        svg(body).shape(None, tolerance)
+    }
+
+    fn svg_shape(body: &str) -> SvgShape {
+        svg_shape_with(body, None).unwrap()
+    }
+
+    fn shape(body: &str) -> Shape {
+        svg_shape(body).shape
+    }
+
+    fn first_fill(shape: &Shape) -> &FillStyle {
+        shape
+            .records
+            .iter()
+            .find_map(|record| match record {
+                ShapeRecord::StyleChange(change) => change.new_styles.as_ref()?.fill.first(),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    /// Pen position after the first move, in twips.
+    fn first_move(shape: &Shape) -> (i32, i32) {
+        shape
+            .records
+            .iter()
+            .find_map(|record| match record {
+                ShapeRecord::StyleChange(change) => change.move_to.as_ref().map(|to| (to.x, to.y)),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn each_path_gets_its_own_fill_and_stroke() {
+        let shape = shape(
+            r#"<rect width="10" height="10" fill="red"/>
+            <path d="M 0 20 L 50 20" fill="none" stroke="blue" stroke-width="2"/>"#,
+        );
+        let styles: Vec<_> = shape
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                ShapeRecord::StyleChange(change) if change.new_styles.is_some() => Some((change.left_fill, change.line_style)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(styles, [(Some(1), None), (None, Some(1))]);
+
+        let FillStyle::Solid(fill) = first_fill(&shape) else {
+            panic!("expected a solid fill");
+        };
+        assert_eq!((fill.color.r, fill.color.g, fill.color.b, fill.color.a), (255, 0, 0, 255));
+        let line = shape
+            .records
+            .iter()
+            .find_map(|record| match record {
+                ShapeRecord::StyleChange(change) => change.new_styles.as_ref()?.line.first(),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(line.width, 40);
+        assert_eq!(shape.records.iter().filter(|record| matches!(record, ShapeRecord::Edge(_))).count(), 5);
+    }
+
+    #[test]
+    fn transforms_are_applied_in_twips() {
+        let shape = shape(r#"<g transform="translate(10 5)"><rect x="1" width="4" height="4" transform="scale(2)"/></g>"#);
+        assert_eq!(first_move(&shape), (240, 100));
+    }
+
+    /// `(scale_x, scale_y, rotate_skew0, rotate_skew1)` in 1/65536 and the translation in twips.
+    fn matrix_parts(matrix: &Matrix) -> (i32, i32, i32, i32, i32, i32) {
+        (
+            matrix.scale_x.epsilons,
+            matrix.scale_y.epsilons,
+            matrix.rotate_skew0.epsilons,
+            matrix.rotate_skew1.epsilons,
+            matrix.translate_x,
+            matrix.translate_y,
+        )
+    }
+
+    const STOPS: &str = r#"<stop offset="0" stop-color="red"/><stop offset="1" stop-color="blue"/>"#;
+
+    #[test]
+    fn linear_gradient_maps_square_onto_gradient_vector() {
+        let shape = shape(&format!(
+            r#"<linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="100" y2="0">{}</linearGradient>
+            <rect width="100" height="100" fill="url(#g)"/>"#,
+            STOPS
+        ));
+        let FillStyle::LinearGradient(fill) = first_fill(&shape) else {
+            panic!("expected a linear gradient");
+        };
+        // 100px across the 32768 twip square is 2000 twips, a scale of 2000 / 32768
+        assert_eq!(matrix_parts(&fill.matrix), (4000, 4000, 0, 0, 1000, 0));
+        assert_eq!(fill.gradient.colors.len(), 2);
+    }
+
+    #[test]
+    fn gradient_transform_rotation_goes_into_skew() {
+        let shape = shape(&format!(
+            r#"<linearGradient id="g" gradientUnits="userSpaceOnUse" x1="0" y1="0" x2="100" y2="0"
+                gradientTransform="rotate(90)">{}</linearGradient>
+            <rect width="100" height="100" fill="url(#g)"/>"#,
+            STOPS
+        ));
+        let FillStyle::LinearGradient(fill) = first_fill(&shape) else {
+            panic!("expected a linear gradient");
+        };
+        assert_eq!(matrix_parts(&fill.matrix), (0, 0, 4000, -4000, 0, 1000));
+    }
+
+    #[test]
+    fn radial_gradient_with_offset_focus_is_focal() {
+        let centered = shape(&format!(
+            r#"<radialGradient id="g" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="50">{}</radialGradient>
+            <rect width="100" height="100" fill="url(#g)"/>"#,
+            STOPS
+        ));
+        let FillStyle::RadialGradient(fill) = first_fill(&centered) else {
+            panic!("expected a radial gradient");
+        };
+        assert_eq!(matrix_parts(&fill.matrix), (4000, 4000, 0, 0, 1000, 1000));
+
+        let focal = shape(&format!(
+            r#"<radialGradient id="g" gradientUnits="userSpaceOnUse" cx="50" cy="50" r="50" fx="75" fy="50">{}</radialGradient>
+            <rect width="100" height="100" fill="url(#g)"/>"#,
+            STOPS
+        ));
+        let FillStyle::FocalGradient(fill) = first_fill(&focal) else {
+            panic!("expected a focal gradient");
+        };
+        assert_eq!(matrix_parts(&fill.matrix), (4000, 4000, 0, 0, 1000, 1000));
+        assert_eq!(fill.focal_point.epsilons, 128);
+    }
+
+    #[test]
+    fn group_opacity_multiplies_into_fill_alpha() {
+        let shape = shape(r#"<g opacity="0.5"><rect width="10" height="10" fill="red" fill-opacity="0.5"/></g>"#);
+        let FillStyle::Solid(fill) = first_fill(&shape) else {
+            panic!("expected a solid fill");
+        };
+        assert_eq!(fill.color.a, 63);
+    }
+
+    #[test]
+    fn only_overlapping_nonzero_fills_need_winding() {
+        let contours = "M 0 0 L 50 0 L 50 50 Z M 10 10 L 40 10 L 40 40 Z";
+        assert!(svg_shape(&format!(r#"<path d="{}" fill-rule="nonzero"/>"#, contours)).fill_winding);
+        assert!(!svg_shape(&format!(r#"<path d="{}" fill-rule="evenodd"/>"#, contours)).fill_winding);
+        assert!(!svg_shape(r#"<path d="M 0 0 L 50 0 L 50 50 Z" fill-rule="nonzero"/>"#).fill_winding);
+    }
+
+    /// Distance from the circle's center to the middle of each curved edge, in twips.
+    fn curve_midpoint_radii(shape: &Shape, center: (f64, f64)) -> Vec<f64> {
+        let mut pen = (0, 0);
+        let mut radii = Vec::new();
+        for record in &shape.records {
+            match record {
+                ShapeRecord::StyleChange(change) => {
+                    if let Some(to) = &change.move_to {
+                        pen = (to.x, to.y);
+                    }
+                }
+                ShapeRecord::Edge(edge) => {
+                    let end = (pen.0 + edge.delta.x, pen.1 + edge.delta.y);
+                    if let Some(control) = &edge.control_delta {
+                        let control = (pen.0 + control.x, pen.1 + control.y);
+                        let mid = |a: i32, c: i32, b: i32| 0.25 * f64::from(a) + 0.5 * f64::from(c) + 0.25 * f64::from(b);
+                        let x = mid(pen.0, control.0, end.0) - center.0;
+                        let y = mid(pen.1, control.1, end.1) - center.1;
+                        radii.push(x.hypot(y));
+                    }
+                    pen = end;
+                }
+            }
+        }
+        radii
+    }
+
+    #[test]
+    fn curves_stay_within_tolerance() {
+        let circle = r#"<circle cx="50" cy="50" r="50" fill="red"/>"#;
+        for tolerance in [0.5, 1.0, 5.0] {
+            let shape = svg_shape_with(circle, Some(tolerance)).unwrap();
+            let radii = curve_midpoint_radii(&shape.shape, (1000.0, 1000.0));
+            assert!(!radii.is_empty());
+            for radius in radii {
+                // Control points are rounded to whole twips, which moves the midpoint by under 1
+                assert!((radius - 1000.0).abs() <= tolerance + 1.0, "radius {} at tolerance {}", radius, tolerance);
+            }
+        }
+
+        let count = |tolerance| curve_midpoint_radii(&svg_shape_with(circle, Some(tolerance)).unwrap().shape, (0.0, 0.0)).len();
+        assert!(count(0.1) > count(10.0));
+    }
+
+    #[test]
+    fn tolerance_must_be_positive() {
+        let circle = r#"<circle cx="50" cy="50" r="50" fill="red"/>"#;
+        assert!(svg_shape_with(circle, Some(0.0)).is_err());
+        assert!(svg_shape_with(circle, Some(f64::NAN)).is_err());
+    }
+
+    #[test]
+    fn svg_id_selects_one_layer_in_place() {
+        let file = svg(
+            r#"<rect id="base" width="100" height="100" fill="blue"/>
+            <g id="hover"><rect x="50" y="50" width="10" height="10" fill="red"/></g>
+            <g opacity="0.5"><rect id="back" width="10" height="10" fill="green"/></g>"#,
+        );
+        assert_eq!(first_move(&file.shape(Some("hover"), None).unwrap().shape), (1000, 1000));
+        let back = file.shape(Some("back"), None).unwrap();
+        let FillStyle::Solid(fill) = first_fill(&back.shape) else {
+            panic!("expected a solid fill");
+        };
+        assert_eq!(fill.color.a, 127);
+        assert!(file.shape(Some("missing"), None).is_err());
+    }
+}
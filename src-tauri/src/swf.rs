@@ -1,32 +1,42 @@
-use kurbo::Point;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::str::FromStr;
-use svgtypes::{Color, PathParser, PathSegment, Transform};
-use swf_emitter::emit_swf;
-use swf_parser::parse_swf;
 use swf_types::{
-    fill_styles, shape_records, CapStyle, FillStyle, JoinStyle, LineStyle, Movie, Rect, SRgb8,
-    Shape, ShapeRecord, ShapeStyles, StraightSRgba8, Tag, text, tags,
+    fill_styles, FillStyle, Movie, Rect, SRgb8, Shape, ShapeRecord, ShapeStyles, StraightSRgba8, Tag, text, tags,
 };
+#[cfg(feature = "gui")]
 use tauri::{command, AppHandle};
-use xmlparser::{Token, Tokenizer};
 use crate::ba2::{Ba2Path, extract_file_from_ba2, is_ba2_path};
+use crate::edit_text::{EditTextProperties, EDIT_TEXT_PROPERTIES};
+use crate::font::{parse_char_set, used_chars, EmbeddedFont, FontFile};
+use crate::deps::{plan_removal, remove_characters, DependencyGraph, RemovedCharacter};
+use crate::error::{from_json_str, Result, StarDeltaError};
+use crate::ids::{Aliases, IdAllocator};
+use crate::svg::{needs_define_shape4, SvgFile, SvgShape};
+use crate::patcher::{emit_movie, parse_movie, ModificationCount, PatchSummary, Patcher, SwfCompression};
+use crate::place::{
+    insert_definition, insert_do_abc, insert_import, insert_place_object, move_before_first_use, Anchor,
+};
+use crate::target::{resolve_all, CharacterRef, Target};
+use crate::validate::ValidationReport;
+use crate::walk::{find_sprite, find_sprite_mut, retain_tags, walk_tags_at, walk_tags_mut, walk_tags_mut_at, TagPath};
 use std::process::Command;
 use tempfile::TempDir;
 
-const SWF_SCALE: f32 = 20.0;  // SWF uses 20 twips per pixel, whereas SVG uses 1px per pixel
 
 #[derive(Debug, Deserialize)]
 pub struct ModificationConfig {
     pub file: Option<Vec<ShapeSource>>,
-    pub transparent: Option<Vec<u16>>,  // Shape IDs to make transparent
+    pub transparent: Option<Vec<CharacterRef>>,  // Shapes to make transparent
     pub actionscript: Option<Vec<ActionScriptPatch>>,  // New field for ActionScript patches
     pub swf: SwfModification,
     pub new_elements: Option<NewElements>,  // New field for adding elements
     pub remove_elements: Option<RemoveElements>,  // New field for removing elements
+    pub compression: Option<SwfCompression>,  // Output compression (defaults to the input's)
+    pub json_patch: Option<json_patch::Patch>,  // RFC 6902 operations on the swf2json tree, applied first
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,11 +45,15 @@ pub struct BatchProcessConfig {
     pub output_directory: String,      // Directory to save processed files
     pub ba2_path: Option<String>,      // User-selected BA2 file path (if using BA2)
     pub swf_mappings: Vec<SwfMapping>, // Mappings from mod names to SWF file paths
+    pub json_dump_directory: Option<String>, // If set, also write each patched movie as JSON here
+    #[serde(default)]
+    pub strict: bool,                  // Fail when a non-optional modification matches nothing
 }
 
 #[derive(Debug, Deserialize)]
 pub struct BatchConfiguration {
     pub mods: Vec<ModConfig>,          // List of modification configurations
+    pub compression: Option<SwfCompression>, // Output compression for every file (per-file configs take precedence)
 }
 
 #[derive(Debug, Deserialize)]
@@ -58,16 +72,41 @@ pub struct FileConfig {
 
 #[derive(Debug, Deserialize)]
 pub struct ShapeSource {
-    source: String,
-    shapes: Vec<u16>,
+    pub(crate) source: String,
+    pub(crate) shapes: Vec<ShapeTarget>,
+    pub(crate) tolerance: Option<f64>,  // Largest curve error in twips, 1 by default
+}
+
+/// A shape replaced from a `ShapeSource`: a character reference takes the whole drawing,
+/// `{"svg_id": "hover", "shape": 3003}` only the SVG element with that ID.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShapeTarget {
+    Element { svg_id: String, shape: CharacterRef },
+    Whole(CharacterRef),
+}
+
+impl ShapeTarget {
+    pub fn shape(&self) -> &CharacterRef {
+        match self {
+            ShapeTarget::Element { shape, .. } | ShapeTarget::Whole(shape) => shape,
+        }
+    }
+
+    pub fn svg_id(&self) -> Option<&str> {
+        match self {
+            ShapeTarget::Element { svg_id, .. } => Some(svg_id.as_str()),
+            ShapeTarget::Whole(_) => None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SwfModification {
-    bounds: Option<Bounds>,
-    modifications: Vec<TagModification>,
-    new_elements: Option<NewElements>,
-    remove_elements: Option<RemoveElements>,
+    pub(crate) bounds: Option<Bounds>,
+    pub(crate) modifications: Vec<TagModification>,
+    pub(crate) new_elements: Option<NewElements>,
+    pub(crate) remove_elements: Option<RemoveElements>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,11 +121,79 @@ pub struct BoundRange {
     pub max: i32,
 }
 
-#[derive(Debug, Deserialize)]
-struct TagModification {
-    tag: String,
-    id: u16,
-    properties: serde_json::Value,
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct TagModification {
+    pub(crate) tag: String,
+    #[serde(default)]
+    pub(crate) id: u16,
+    pub(crate) target: Option<Target>,  // Resolved to `id` against the movie when set
+    pub(crate) scope: Option<TimelineScope>,  // Only for timeline tags, see `is_timeline_tag`
+    pub(crate) properties: serde_json::Value,
+    #[serde(default)]
+    pub(crate) optional: bool,  // Don't fail in strict mode if nothing matches
+    #[serde(default)]
+    pub(crate) mode: ModificationMode,
+}
+
+/// Restricts a timeline modification (`PlaceObjectTag`, `RemoveObjectTag`, `FrameLabelTag`,
+/// `DoActionTag`) to part of a timeline. Without a scope every such tag in the root
+/// timeline matches.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct TimelineScope {
+    pub(crate) sprite: Option<CharacterRef>,     // Timeline of this sprite instead of the root
+    #[serde(default)]
+    pub(crate) recursive: bool,                  // Also visit sprites defined inside that timeline
+    pub(crate) frame: Option<usize>,             // 0-based frame index
+    pub(crate) depth: Option<u16>,
+    pub(crate) character: Option<CharacterRef>,  // Character placed at the tag's depth
+}
+
+/// `TimelineScope` filters with character references resolved.
+struct ScopeFilter {
+    frame: Option<usize>,
+    depth: Option<u16>,
+    character: Option<u16>,
+}
+
+impl ScopeFilter {
+    fn matches(&self, frame: usize, depth: Option<u16>, character: Option<u16>) -> bool {
+        self.frame.is_none_or(|f| f == frame)
+            && self.depth.is_none_or(|d| depth == Some(d))
+            && self.character.is_none_or(|c| character == Some(c))
+    }
+}
+
+impl TagModification {
+    /// Returns this modification with `target`, if set, resolved to a numeric `id` and any
+    /// `"@alias"` IDs in `properties` replaced.
+    pub(crate) fn resolve(&self, movie: &Movie, aliases: &Aliases) -> Result<Cow<'_, TagModification>> {
+        let mut properties = self.properties.clone();
+        aliases.substitute(&mut properties)?;
+        if self.target.is_none() && properties == self.properties {
+            return Ok(Cow::Borrowed(self));
+        }
+        let id = match &self.target {
+            Some(target) => target.resolve(movie)?,
+            None => self.id,
+        };
+        Ok(Cow::Owned(TagModification {
+            id,
+            target: None,
+            properties,
+            ..self.clone()
+        }))
+    }
+}
+
+/// How a modification's `properties` are applied to the matched tags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ModificationMode {
+    /// Only the properties listed by `supported_properties`, under their config names
+    #[default]
+    Fields,
+    /// RFC 7386 merge patch over the tag's swf-types JSON, so any field can be changed
+    Merge,
 }
 
 #[derive(Debug, Deserialize)]
@@ -103,28 +210,42 @@ pub struct NewElements {
     pub bitmaps: Option<Vec<NewBitmap>>,
     pub buttons: Option<Vec<NewButton>>,
     pub scenes: Option<Vec<NewScene>>,
+    pub fonts: Option<Vec<NewFont>>,
+    pub imports: Option<Vec<NewImport>>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NewShape {
     pub source: String,           // Path to SVG source
     pub id: Option<u16>,         // Optional ID (if not provided, will auto-generate)
+    pub alias: Option<String>,    // Name for "@alias" references elsewhere in the config
     pub bounds: Option<Bounds>,   // Optional bounds override
+    pub place: Option<Placement>, // Optional placement on a timeline
+    pub before: Option<Anchor>,   // Explicit insertion point for the definition
+    pub tolerance: Option<f64>,   // Largest curve error in twips, 1 by default
+    pub svg_id: Option<String>,   // SVG element to draw instead of the whole file
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NewSprite {
     pub id: Option<u16>,
+    pub alias: Option<String>,
     pub frame_count: u16,
-    pub tags: Vec<Tag>,
+    pub tags: Vec<serde_json::Value>,  // swf-types tags, read once "@alias" IDs are known
+    pub place: Option<Placement>,
+    pub before: Option<Anchor>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NewText {
     pub id: Option<u16>,
+    pub alias: Option<String>,
+    pub place: Option<Placement>,
+    pub before: Option<Anchor>,
     pub text: String,
     pub bounds: Bounds,
-    pub font_class: String,
+    pub font_id: Option<CharacterRef>,  // Embedded font by ID, "@alias" or {"font": "name"}
+    pub font_class: Option<String>,     // ActionScript font class, instead of font_id
     pub font_size: u16,
     pub color: Option<StraightSRgba8>,
     pub word_wrap: bool,
@@ -133,6 +254,14 @@ pub struct NewText {
     pub no_select: bool,
     pub html: bool,
     pub use_outlines: bool,
+    #[serde(default)]
+    pub auto_size: bool,
+    #[serde(default)]
+    pub border: bool,
+    #[serde(default)]
+    pub password: bool,
+    pub max_length: Option<u16>,
+    pub variable_name: Option<String>,  // ActionScript variable bound to the text
     pub align: text::TextAlignment,
     pub margin_left: u16,
     pub margin_right: u16,
@@ -140,28 +269,75 @@ pub struct NewText {
     pub leading: i16,
 }
 
+/// Where to show a new shape, sprite or text: a `PlaceObject` added at the end of `frame`.
+#[derive(Debug, Deserialize)]
+pub struct Placement {
+    pub sprite: Option<CharacterRef>,  // Sprite to place in, the root timeline if omitted
+    #[serde(default)]
+    pub frame: usize,                  // 0-based frame index
+    pub depth: u16,
+    pub matrix: Option<swf_types::Matrix>,
+    pub name: Option<String>,          // Instance name
+    pub color_transform: Option<swf_types::ColorTransformWithAlpha>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RemoveElements {
-    pub shapes: Option<Vec<u16>>,      // Shape IDs to remove
-    pub sprites: Option<Vec<u16>>,     // Sprite IDs to remove
-    pub texts: Option<Vec<u16>>,       // Text IDs to remove
-    pub buttons: Option<Vec<u16>>,     // Button IDs to remove
-    pub bitmaps: Option<Vec<u16>>,     // Bitmap IDs to remove
+    pub shapes: Option<Vec<CharacterRef>>,   // Shapes to remove
+    pub sprites: Option<Vec<CharacterRef>>,  // Sprites to remove
+    pub texts: Option<Vec<CharacterRef>>,    // Texts to remove
+    pub buttons: Option<Vec<CharacterRef>>,  // Buttons to remove
+    pub bitmaps: Option<Vec<CharacterRef>>,  // Bitmaps to remove
     pub frames: Option<Vec<String>>,   // Frame labels to remove
     pub scenes: Option<Vec<String>>,   // Scene names to remove
+    #[serde(default)]
+    pub cascade: bool,                 // Also remove dependents and characters left unused
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NewBitmap {
     pub id: Option<u16>,
+    pub alias: Option<String>,
     pub width: u16,
     pub height: u16,
     pub data: Vec<u8>,
+    pub before: Option<Anchor>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct NewButton {
-    pub states: Vec<swf_types::tags::DefineButton>,
+    pub states: Vec<serde_json::Value>,  // swf-types DefineButton tags, read once "@alias" IDs are known
+    pub before: Option<Anchor>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewFont {
+    pub source: String,           // Path to a TTF or OTF file
+    pub id: Option<u16>,
+    pub alias: Option<String>,
+    pub name: Option<String>,     // Font name, the family name in the file if omitted
+    #[serde(default)]
+    pub chars: Vec<String>,       // "U+0400-U+04FF" ranges, "U+00E9" code points or literal characters
+    #[serde(default)]
+    pub used_chars: bool,         // Also embed every character shown by texts using the font
+    pub bold: Option<bool>,       // Style flags, read from the file if omitted
+    pub italic: Option<bool>,
+    pub before: Option<Anchor>,
+}
+
+/// Characters imported from another SWF, such as the game's font libraries.
+#[derive(Debug, Deserialize)]
+pub struct NewImport {
+    pub url: String,                 // Library SWF, e.g. "fonts_en.swf"
+    pub assets: Vec<ImportedAsset>,
+    pub before: Option<Anchor>,      // Explicit insertion point for a new ImportAssets2 tag
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ImportedAsset {
+    pub name: String,                // Name the library exports the character under
+    pub id: Option<u16>,             // Local ID (if not provided, will auto-generate)
+    pub alias: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -177,11 +353,12 @@ pub struct ActionScriptPatch {
     pub class_name: Option<String>,    // Optional class name for replacement
     pub package_name: Option<String>,  // Optional package name
     pub symbol_bindings: Option<Vec<SymbolBinding>>,  // Optional symbol class bindings
+    pub before: Option<Anchor>,        // Explicit insertion point for a new DoABC tag
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SymbolBinding {
-    pub symbol_id: u16,                // The ID of the symbol (shape, sprite, etc.)
+    pub symbol_id: CharacterRef,       // The symbol (shape, sprite, etc.) by ID, selector or "@alias"
     pub class_name: String,            // The fully qualified class name to bind to
 }
 
@@ -193,879 +370,307 @@ pub enum ActionScriptInsertMode {
     Replace  // Replace an existing script with matching class name
 }
 
-fn read_swf_file(path: &str) -> Result<Vec<u8>, String> {
+/// Reads SWF bytes from disk, or out of a BA2 archive for `archive.ba2//path/in/archive.swf` paths.
+pub fn read_swf_file(path: &str) -> Result<Vec<u8>> {
     if is_ba2_path(path) {
         if let Some(ba2_path) = Ba2Path::from_string(path) {
             extract_file_from_ba2(&ba2_path)
         } else {
-            Err("Invalid BA2 path format".to_string())
+            Err(StarDeltaError::config(format!("Invalid BA2 path format: {}", path)))
         }
     } else {
-        fs::read(path).map_err(|e| format!("Failed to read SWF file: {}", e))
+        fs::read(path).map_err(|e| StarDeltaError::io("Failed to read SWF file", path, e))
     }
 }
 
+#[cfg(feature = "gui")]
 #[command]
 pub fn convert_swf_to_json(
     _handle: AppHandle,
     swf_path: String,
     json_path: String,
-) -> Result<(), String> {
-    let swf_data = read_swf_file(&swf_path)?;
-    let movie = parse_swf(&swf_data).map_err(|e| format!("Failed to parse SWF: {}", e))?;
+) -> Result<SwfCompression> {
+    swf_to_json(&swf_path, &json_path)
+}
+
+/// Parses an SWF (from disk or a `archive.ba2//path` reference) and writes it as JSON.
+/// Returns the compression of the source file, which the JSON itself does not record.
+pub fn swf_to_json(swf_path: &str, json_path: &str) -> Result<SwfCompression> {
+    let swf_data = read_swf_file(swf_path)?;
+    let movie = parse_movie(&swf_data)?;
     let json = serde_json::to_string_pretty(&movie)
-        .map_err(|e| format!("Failed to convert to JSON: {}", e))?;
-    fs::write(json_path, json).map_err(|e| format!("Failed to write JSON file: {}", e))?;
-    Ok(())
+        .map_err(|e| StarDeltaError::JsonSerialize { message: e.to_string() })?;
+    fs::write(json_path, json).map_err(|e| StarDeltaError::io("Failed to write JSON file", json_path, e))?;
+    Ok(SwfCompression::detect(&swf_data))
 }
 
+#[cfg(feature = "gui")]
 #[command]
 pub fn apply_json_modifications(
     _handle: AppHandle,
     swf_json_path: String,
     config_json_path: String,
     output_json_path: String,
-) -> Result<(), String> {
+) -> Result<Option<SwfCompression>> {
+    modify_swf_json(&swf_json_path, &config_json_path, &output_json_path)
+}
+
+/// Applies a modification config to an SWF JSON dump and writes the result to `output_json_path`.
+/// Returns the config's compression override, if any.
+pub fn modify_swf_json(
+    swf_json_path: &str,
+    config_json_path: &str,
+    output_json_path: &str,
+) -> Result<Option<SwfCompression>> {
     println!("Starting JSON modifications process...");
     println!("SWF JSON path: {}", swf_json_path);
     println!("Config JSON path: {}", config_json_path);
     println!("Output JSON path: {}", output_json_path);
 
     // Read SWF JSON
-    let swf_json = fs::read_to_string(&swf_json_path).map_err(|e| {
+    let swf_json = fs::read_to_string(swf_json_path).map_err(|e| {
         println!("Failed to read SWF JSON file '{}': {}", swf_json_path, e);
-        format!("Failed to read SWF JSON file: {}", e)
+        StarDeltaError::io("Failed to read SWF JSON file", swf_json_path, e)
     })?;
 
-    let mut movie: Movie = serde_json::from_str(&swf_json).map_err(|e| {
+    let mut movie: Movie = from_json_str(&swf_json, swf_json_path).inspect_err(|e| {
         println!("Failed to parse SWF JSON file '{}': {}", swf_json_path, e);
-        format!("Failed to parse SWF JSON file: {}", e)
     })?;
 
-    // Read config JSON
-    let config_json = fs::read_to_string(&config_json_path).map_err(|e| {
-        println!("Failed to read config JSON file '{}': {}", config_json_path, e);
-        format!("Failed to read config JSON file: {}", e)
-    })?;
+    let patcher = Patcher::from_file(config_json_path)?;
+    let summary = patcher.apply(&mut movie)?;
+    print!("{}", summary);
 
-    println!("Config JSON content: {}", config_json);
+    // Write modified JSON
+    let modified_json = serde_json::to_string_pretty(&movie).map_err(|e| {
+        println!("Failed to serialize modified JSON: {}", e);
+        StarDeltaError::JsonSerialize { message: e.to_string() }
+    })?;
 
-    let config: ModificationConfig = serde_json::from_str(&config_json).map_err(|e| {
-        println!("Failed to parse config JSON file '{}': {}", config_json_path, e);
-        format!("Failed to parse config JSON file: {}", e)
+    fs::write(output_json_path, modified_json).map_err(|e| {
+        println!("Failed to write modified JSON file '{}': {}", output_json_path, e);
+        StarDeltaError::io("Failed to write modified JSON file", output_json_path, e)
     })?;
 
-    // Apply transparency if specified
+    println!("JSON modifications completed successfully");
+    Ok(patcher.config().compression)
+}
+
+/// Runs every step of a modification config against `movie`. Relative asset paths
+/// (SVG and ActionScript sources) are resolved against `base_dir`.
+pub(crate) fn apply_modification_config(
+    movie: &mut Movie,
+    config: &ModificationConfig,
+    base_dir: &Path,
+) -> Result<PatchSummary> {
+    // Apply JSON patch operations first so their paths match the unmodified swf2json output
+    if let Some(patch) = &config.json_patch {
+        println!("Applying JSON patch operations...");
+        apply_json_patch(movie, patch).inspect_err(|e| {
+            println!("Error applying JSON patch: {}", e);
+        })?;
+    }
+
+    // Pick the IDs of all new elements up front so any step can refer to them by alias
+    let ids = plan_new_element_ids(movie, config)?;
+    let aliases = ids.aliases();
+
+    // Apply transparency if specified; new shapes are made transparent once they are added
+    let mut new_transparent = Vec::new();
     if let Some(transparent_shapes) = &config.transparent {
         println!("Applying transparency...");
-        if let Err(e) = apply_transparency(&mut movie, transparent_shapes) {
+        let (new_shapes, shape_ids) = resolve_all(movie, aliases, transparent_shapes)?
+            .into_iter()
+            .partition(|&id| ids.is_new(id));
+        new_transparent = new_shapes;
+        apply_transparency(movie, &shape_ids).inspect_err(|e| {
             println!("Error applying transparency: {}", e);
-            return Err(format!("Failed to apply transparency: {}", e));
-        }
+        })?;
     }
 
-    // Apply shape replacements if specified
+    // Apply shape replacements if specified, keeping those of new shapes for later
+    let mut new_replacements = Vec::new();
     if let Some(shape_sources) = &config.file {
         println!("Applying shape replacements...");
-        if let Err(e) = apply_shape_replacements(&mut movie, shape_sources, &config_json_path) {
+        new_replacements = apply_shape_replacements(movie, shape_sources, &ids, base_dir).inspect_err(|e| {
             println!("Error applying shape replacements: {}", e);
-            return Err(format!("Failed to apply shape replacements: {}", e));
-        }
+        })?;
     }
 
     // Apply ActionScript patches if specified
     if let Some(actionscript_patches) = &config.actionscript {
         println!("Applying ActionScript patches...");
-        if let Err(e) = apply_actionscript_patches(&mut movie, actionscript_patches, &config_json_path, _handle) {
+        apply_actionscript_patches(movie, actionscript_patches, aliases, base_dir).inspect_err(|e| {
             println!("Error applying ActionScript patches: {}", e);
-            return Err(format!("Failed to apply ActionScript patches: {}", e));
-        }
+        })?;
     }
 
     // Apply other modifications
     println!("Applying SWF modifications...");
-    if let Err(e) = apply_modifications(&mut movie, &config.swf, Path::new(&config_json_path)) {
+    let mut summary = apply_modifications(movie, &config.swf, &ids, base_dir).inspect_err(|e| {
         println!("Error applying modifications: {}", e);
-        return Err(format!("Failed to apply modifications: {}", e));
-    }
+    })?;
 
     // Handle new elements from the root config if present
     if let Some(new_elements) = &config.new_elements {
         println!("Applying new elements from root config...");
-        add_new_elements(&mut movie, new_elements)?;
+        add_new_elements(movie, new_elements, "new_elements", &ids, base_dir)?;
+    }
+
+    // Every new element exists now, so `transparent` and `file` can reach the new shapes
+    if !new_transparent.is_empty() {
+        println!("Applying transparency to new shapes...");
+        apply_transparency(movie, &new_transparent)?;
+    }
+    for (shape_id, shape) in &new_replacements {
+        replace_shape_in_movie(movie, *shape_id, shape)?;
     }
 
-    // Handle element removal from both root config and swf config
+    // Handle element removal from the root config; `apply_modifications` already ran the swf one
     if let Some(remove_elements) = &config.remove_elements {
         println!("Applying element removal from root config...");
-        remove_swf_elements(&mut movie, remove_elements)?;
-    }
-    if let Some(remove_elements) = &config.swf.remove_elements {
-        println!("Applying element removal from swf config...");
-        remove_swf_elements(&mut movie, remove_elements)?;
+        summary.removed.extend(remove_swf_elements(movie, remove_elements, aliases)?);
     }
 
-    // Write modified JSON
-    let modified_json = serde_json::to_string_pretty(&movie).map_err(|e| {
-        println!("Failed to serialize modified JSON: {}", e);
-        format!("Failed to serialize modified JSON: {}", e)
-    })?;
+    // Glyphs of new fonts are embedded once texts are final, for fonts with `used_chars`
+    embed_new_fonts(movie, config, &ids, base_dir)?;
 
-    fs::write(&output_json_path, modified_json).map_err(|e| {
-        println!("Failed to write modified JSON file '{}': {}", output_json_path, e);
-        format!("Failed to write modified JSON file: {}", e)
-    })?;
+    summary.assigned = ids.into_assigned();
+    Ok(summary)
+}
 
-    println!("JSON modifications completed successfully");
+/// Applies RFC 6902 operations to the movie's JSON representation (as written by
+/// `convert_swf_to_json`) and reads the result back.
+pub(crate) fn apply_json_patch(movie: &mut Movie, patch: &json_patch::Patch) -> Result<()> {
+    let mut value = serde_json::to_value(&*movie)
+        .map_err(|e| StarDeltaError::JsonSerialize { message: e.to_string() })?;
+    json_patch::patch(&mut value, patch).map_err(|e| StarDeltaError::JsonPatch {
+        operation: Some(e.operation),
+        path: e.path.to_string(),
+        message: e.kind.to_string(),
+    })?;
+    *movie = serde_path_to_error::deserialize(value).map_err(|e| StarDeltaError::JsonPatch {
+        operation: None,
+        path: e.path().to_string(),
+        message: e.into_inner().to_string(),
+    })?;
     Ok(())
 }
 
-fn apply_shape_replacements(movie: &mut Movie, sources: &[ShapeSource], config_path: &str) -> Result<(), String> {
-    // Get the config file's directory
-    let config_dir = Path::new(config_path)
-        .parent()
-        .ok_or_else(|| "Could not determine config file directory".to_string())?;
-
+/// Replaces the existing shapes named in `sources` and returns the converted drawings for
+/// new elements, which are only added later.
+fn apply_shape_replacements(
+    movie: &mut Movie,
+    sources: &[ShapeSource],
+    ids: &IdAllocator,
+    config_dir: &Path,
+) -> Result<Vec<(u16, SvgShape)>> {
+    let mut new_shapes = Vec::new();
     for source in sources {
         // Resolve the source path relative to the config file's directory
         let source_path = config_dir.join(&source.source);
-        let shapes = parse_shape_source(&source_path)
-            .map_err(|e| format!("Failed to parse shape source '{}': {}", source.source, e))?;
-
-        // Replace each specified shape ID with the new shape
-        for &shape_id in &source.shapes {
-            replace_shape_in_movie(movie, shape_id, shapes.as_slice())
-                .map_err(|e| format!("Failed to replace shape {}: {}", shape_id, e))?;
-        }
-    }
-    Ok(())
-}
-
-fn point_to_vec2d(from: Point, to: Point) -> swf_types::Vector2D {
-    swf_types::Vector2D {
-        x: ((to.x * SWF_SCALE as f64) as i32 - (from.x * SWF_SCALE as f64) as i32),
-        y: ((to.y * SWF_SCALE as f64) as i32 - (from.y * SWF_SCALE as f64) as i32),
-    }
-}
-
-fn opacity_to_alpha(opacity: f32) -> u8 {
-    (opacity.clamp(0.0, 1.0) * 255.0) as u8
-}
-
-fn apply_transform(point: Point, transform: &Transform) -> Point {
-    Point::new(
-        transform.a * point.x + transform.c * point.y + transform.e,
-        transform.b * point.x + transform.d * point.y + transform.f,
-    )
-}
-
-fn parse_shape_source(path: &Path) -> Result<Vec<Shape>, String> {
-    println!("Starting to parse SVG file: {}", path.display());
-    let svg_data = fs::read(path).map_err(|e| format!("Failed to read SVG file: {}", e))?;
-
-    let mut shapes = Vec::new();
-    let mut current_shape = Shape {
-        initial_styles: ShapeStyles {
-            fill: Vec::new(),
-            line: Vec::new(),
-        },
-        records: Vec::new(),
-    };
-
-    let xml = String::from_utf8_lossy(&svg_data);
-    let mut tokenizer = Tokenizer::from(xml.as_ref());
-
-    let mut in_path = false;
-    let mut group_transform: Option<Transform> = None;
-    let mut path_transform: Option<Transform> = None;
-    let mut path_data: Option<String> = None;
-    let mut fill_color: Option<Color> = None;
-    let mut stroke_color: Option<Color> = None;
-    let mut stroke_width = 1.0;
-    let mut fill_opacity = 1.0;
-    let mut stroke_opacity = 1.0;
-    let mut path_count = 0;
-    let mut current_fill_style_index = 0;
-    let mut current_line_style_index = 0;
-
-    println!("Starting XML parsing");
-    while let Some(token) = tokenizer.next() {
-        let token = token.map_err(|e| format!("Failed to parse SVG: {}", e))?;
-        match token {
-            Token::ElementStart { local, .. } => {
-                if local.as_str() == "path" {
-                    path_count += 1;
-                    println!("Found path #{}", path_count);
-                    in_path = true;
-                    path_transform = None;
-                    path_data = None;
-                    fill_color = None;
-                    stroke_color = None;
-                    stroke_width = 1.0;
-                    fill_opacity = 1.0;
-                    stroke_opacity = 1.0;
-                } else if local.as_str() == "g" {
-                    println!("Found group element");
-                }
-            }
-            Token::Attribute { local, value, .. } => {
-                match local.as_str() {
-                    "transform" => {
-                        println!("Found transform: {}", value.as_str());
-                        let transform = Transform::from_str(value.as_str()).ok();
-                        if in_path {
-                            path_transform = transform;
-                        } else {
-                            group_transform = transform;
-                        }
-                    }
-                    "d" if in_path => {
-                        println!("Found path data");
-                        path_data = Some(value.as_str().to_string());
-                    }
-                    "fill" if in_path => {
-                        println!("Found fill color: {}", value.as_str());
-                        fill_color = Color::from_str(value.as_str()).ok();
-                    }
-                    "fill-opacity" => {
-                        if let Ok(n) = value.as_str().parse::<f32>() {
-                            fill_opacity = n;
-                        }
-                    }
-                    "stroke" => {
-                        stroke_color = match value.as_str() {
-                            "none" => None,
-                            color_str => Color::from_str(color_str).ok(),
-                        };
-                    }
-                    "stroke-width" => {
-                        if let Ok(n) = value.as_str().parse::<f32>() {
-                            stroke_width = n;
-                        }
-                    }
-                    "stroke-opacity" => {
-                        if let Ok(n) = value.as_str().parse::<f32>() {
-                            stroke_opacity = n;
-                        }
-                    }
-                    _ => {}
-                }
-            }
-            Token::ElementEnd { .. } if in_path => {
-                in_path = false;
-
-                // Process path data if available
-                if let Some(path_str) = path_data.take() {
-                    println!("Processing path with {} characters", path_str.len());
-                    let mut current_pos = Point::new(0.0, 0.0);
-                    let path_parser = PathParser::from(path_str.as_str());
-
-                    // Add new styles if needed for this path
-                    let mut new_styles = ShapeStyles {
-                        fill: Vec::new(),
-                        line: Vec::new(),
-                    };
-
-                    // Add fill style if one is defined
-                    if let Some(color) = fill_color {
-                        current_fill_style_index += 1;
-                        new_styles.fill.push(FillStyle::Solid(fill_styles::Solid {
-                            color: StraightSRgba8 {
-                                r: color.red,
-                                g: color.green,
-                                b: color.blue,
-                                a: opacity_to_alpha(fill_opacity),
-                            },
-                        }));
-                    }
-
-                    // Add line style if stroke is defined
-                    if let Some(stroke) = stroke_color {
-                        current_line_style_index += 1;
-                        new_styles.line.push(LineStyle {
-                            width: (stroke_width * SWF_SCALE) as u16,
-                            start_cap: CapStyle::Round,
-                            end_cap: CapStyle::Round,
-                            join: JoinStyle::Round,
-                            no_h_scale: false,
-                            no_v_scale: false,
-                            no_close: false,
-                            pixel_hinting: false,
-                            fill: FillStyle::Solid(fill_styles::Solid {
-                                color: StraightSRgba8 {
-                                    r: stroke.red,
-                                    g: stroke.green,
-                                    b: stroke.blue,
-                                    a: opacity_to_alpha(stroke_opacity),
-                                },
-                            }),
-                        });
-                    }
-
-                    // Only add the style change record if we have new styles
-                    if !new_styles.fill.is_empty() || !new_styles.line.is_empty() {
-                        current_shape.records.push(ShapeRecord::StyleChange(
-                            shape_records::StyleChange {
-                                move_to: None,
-                                left_fill: if !new_styles.fill.is_empty() { Some(current_fill_style_index) } else { None },
-                                right_fill: None,
-                                line_style: if !new_styles.line.is_empty() { Some(current_line_style_index) } else { None },
-                                new_styles: Some(new_styles),
-                            },
-                        ));
-                    }
-
-                    // Process path segments
-                    let mut last_control_point: Option<Point> = None;
-                    for segment in path_parser {
-                        let segment = segment.map_err(|e| format!("Failed to parse path: {}", e))?;
-                        match segment {
-                            PathSegment::MoveTo { abs, x, y } => {
-                                let point = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                let transformed_point = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(point, t))
-                                    .unwrap_or(point);
-                                let transformed_point = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_point, t))
-                                    .unwrap_or(transformed_point);
-
-                                current_shape.records.push(ShapeRecord::StyleChange(
-                                    shape_records::StyleChange {
-                                        move_to: Some(swf_types::Vector2D {
-                                            x: (transformed_point.x * SWF_SCALE as f64) as i32,
-                                            y: (transformed_point.y * SWF_SCALE as f64) as i32,
-                                        }),
-                                        right_fill: None,
-                                        left_fill: None,
-                                        line_style: None,
-                                        new_styles: None,
-                                    },
-                                ));
-
-                                current_pos = transformed_point;
-                                last_control_point = None;
-                            },
-                            PathSegment::LineTo { abs, x, y } => {
-                                let point = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                let transformed_point = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(point, t))
-                                    .unwrap_or(point);
-                                let transformed_point = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_point, t))
-                                    .unwrap_or(transformed_point);
-
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, transformed_point),
-                                        control_delta: None,
-                                    },
-                                ));
-
-                                current_pos = transformed_point;
-                                last_control_point = None;
-                            },
-                            PathSegment::HorizontalLineTo { abs, x } => {
-                                let point = if abs {
-                                    Point::new(x as f64, current_pos.y)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y)
-                                };
-
-                                let transformed_point = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(point, t))
-                                    .unwrap_or(point);
-                                let transformed_point = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_point, t))
-                                    .unwrap_or(transformed_point);
-
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, transformed_point),
-                                        control_delta: None,
-                                    },
-                                ));
-
-                                current_pos = transformed_point;
-                                last_control_point = None;
-                            },
-                            PathSegment::VerticalLineTo { abs, y } => {
-                                let point = if abs {
-                                    Point::new(current_pos.x, y as f64)
-                                } else {
-                                    Point::new(current_pos.x, current_pos.y + y as f64)
-                                };
-
-                                let transformed_point = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(point, t))
-                                    .unwrap_or(point);
-                                let transformed_point = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_point, t))
-                                    .unwrap_or(transformed_point);
-
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, transformed_point),
-                                        control_delta: None,
-                                    },
-                                ));
-
-                                current_pos = transformed_point;
-                                last_control_point = None;
-                            },
-                            PathSegment::CurveTo { abs, x1, y1, x2, y2, x, y } => {
-                                let control1 = if abs {
-                                    Point::new(x1 as f64, y1 as f64)
-                                } else {
-                                    Point::new(current_pos.x + x1 as f64, current_pos.y + y1 as f64)
-                                };
-                                let control2 = if abs {
-                                    Point::new(x2 as f64, y2 as f64)
-                                } else {
-                                    Point::new(current_pos.x + x2 as f64, current_pos.y + y2 as f64)
-                                };
-                                let end = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                // Transform all points
-                                let transformed_control1 = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control1, t))
-                                    .unwrap_or(control1);
-                                let transformed_control1 = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control1, t))
-                                    .unwrap_or(transformed_control1);
-
-                                let transformed_control2 = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control2, t))
-                                    .unwrap_or(control2);
-                                let transformed_control2 = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control2, t))
-                                    .unwrap_or(transformed_control2);
-
-                                let transformed_end = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(end, t))
-                                    .unwrap_or(end);
-                                let transformed_end = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_end, t))
-                                    .unwrap_or(transformed_end);
-
-                                // Convert cubic to two quadratic curves
-                                let mid = Point::new(
-                                    (transformed_control1.x + transformed_control2.x) / 2.0,
-                                    (transformed_control1.y + transformed_control2.y) / 2.0
-                                );
-
-                                // First quadratic curve
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, mid),
-                                        control_delta: Some(point_to_vec2d(current_pos, transformed_control1)),
-                                    },
-                                ));
-
-                                // Second quadratic curve
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(mid, transformed_end),
-                                        control_delta: Some(point_to_vec2d(mid, transformed_control2)),
-                                    },
-                                ));
-
-                                current_pos = transformed_end;
-                                last_control_point = Some(transformed_control2);
-                            },
-                            PathSegment::SmoothCurveTo { abs, x2, y2, x, y } => {
-                                let control1 = match last_control_point {
-                                    Some(last_ctrl) => Point::new(
-                                        2.0 * current_pos.x - last_ctrl.x,
-                                        2.0 * current_pos.y - last_ctrl.y
-                                    ),
-                                    None => current_pos
-                                };
-
-                                let control2 = if abs {
-                                    Point::new(x2 as f64, y2 as f64)
-                                } else {
-                                    Point::new(current_pos.x + x2 as f64, current_pos.y + y2 as f64)
-                                };
-                                let end = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                // Transform all points
-                                let transformed_control1 = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control1, t))
-                                    .unwrap_or(control1);
-                                let transformed_control1 = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control1, t))
-                                    .unwrap_or(transformed_control1);
-
-                                let transformed_control2 = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control2, t))
-                                    .unwrap_or(control2);
-                                let transformed_control2 = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control2, t))
-                                    .unwrap_or(transformed_control2);
-
-                                let transformed_end = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(end, t))
-                                    .unwrap_or(end);
-                                let transformed_end = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_end, t))
-                                    .unwrap_or(transformed_end);
-
-                                // Convert cubic to two quadratic curves
-                                let mid = Point::new(
-                                    (transformed_control1.x + transformed_control2.x) / 2.0,
-                                    (transformed_control1.y + transformed_control2.y) / 2.0
-                                );
-
-                                // First quadratic curve
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, mid),
-                                        control_delta: Some(point_to_vec2d(current_pos, transformed_control1)),
-                                    },
-                                ));
-
-                                // Second quadratic curve
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(mid, transformed_end),
-                                        control_delta: Some(point_to_vec2d(mid, transformed_control2)),
-                                    },
-                                ));
-
-                                current_pos = transformed_end;
-                                last_control_point = Some(transformed_control2);
-                            },
-                            PathSegment::Quadratic { abs, x1, y1, x, y } => {
-                                let control = if abs {
-                                    Point::new(x1 as f64, y1 as f64)
-                                } else {
-                                    Point::new(current_pos.x + x1 as f64, current_pos.y + y1 as f64)
-                                };
-                                let end = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                // Transform points
-                                let transformed_control = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control, t))
-                                    .unwrap_or(control);
-                                let transformed_control = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control, t))
-                                    .unwrap_or(transformed_control);
-
-                                let transformed_end = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(end, t))
-                                    .unwrap_or(end);
-                                let transformed_end = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_end, t))
-                                    .unwrap_or(transformed_end);
-
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, transformed_end),
-                                        control_delta: Some(point_to_vec2d(current_pos, transformed_control)),
-                                    },
-                                ));
-
-                                current_pos = transformed_end;
-                                last_control_point = Some(transformed_control);
-                            },
-                            PathSegment::SmoothQuadratic { abs, x, y } => {
-                                let control = match last_control_point {
-                                    Some(last_ctrl) => Point::new(
-                                        2.0 * current_pos.x - last_ctrl.x,
-                                        2.0 * current_pos.y - last_ctrl.y
-                                    ),
-                                    None => current_pos
-                                };
-                                let end = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                // Transform points
-                                let transformed_control = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(control, t))
-                                    .unwrap_or(control);
-                                let transformed_control = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_control, t))
-                                    .unwrap_or(transformed_control);
-
-                                let transformed_end = path_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(end, t))
-                                    .unwrap_or(end);
-                                let transformed_end = group_transform
-                                    .as_ref()
-                                    .map(|t| apply_transform(transformed_end, t))
-                                    .unwrap_or(transformed_end);
-
-                                current_shape.records.push(ShapeRecord::Edge(
-                                    shape_records::Edge {
-                                        delta: point_to_vec2d(current_pos, transformed_end),
-                                        control_delta: Some(point_to_vec2d(current_pos, transformed_control)),
-                                    },
-                                ));
-
-                                current_pos = transformed_end;
-                                last_control_point = Some(transformed_control);
-                            },
-                            PathSegment::ClosePath { .. } => {
-                                if let Some(first_record) = current_shape.records.first() {
-                                    if let ShapeRecord::StyleChange(style_change) = first_record {
-                                        if let Some(move_to) = style_change.move_to {
-                                            let first_point = Point::new(move_to.x as f64, move_to.y as f64);
-                                            current_shape.records.push(ShapeRecord::Edge(
-                                                shape_records::Edge {
-                                                    delta: point_to_vec2d(current_pos, first_point),
-                                                    control_delta: None,
-                                                },
-                                            ));
-                                            current_pos = first_point;
-                                        }
-                                    }
-                                }
-                                last_control_point = None;
-                            },
-                            PathSegment::EllipticalArc { abs, rx, ry, x_axis_rotation, large_arc, sweep, x, y } => {
-                                let end_point = if abs {
-                                    Point::new(x as f64, y as f64)
-                                } else {
-                                    Point::new(current_pos.x + x as f64, current_pos.y + y as f64)
-                                };
-
-                                if rx == 0.0 || ry == 0.0 {
-                                    let transformed_end = path_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(end_point, t))
-                                        .unwrap_or(end_point);
-                                    let transformed_end = group_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(transformed_end, t))
-                                        .unwrap_or(transformed_end);
-
-                                    current_shape.records.push(ShapeRecord::Edge(
-                                        shape_records::Edge {
-                                            delta: point_to_vec2d(current_pos, transformed_end),
-                                            control_delta: None,
-                                        },
-                                    ));
-                                    current_pos = transformed_end;
-                                    continue;
-                                }
-
-                                let rx = rx.abs();
-                                let ry = ry.abs();
-                                let x_axis_rotation = x_axis_rotation.to_radians();
-
-                                let dx = (current_pos.x - end_point.x) / 2.0;
-                                let dy = (current_pos.y - end_point.y) / 2.0;
-
-                                let cos_phi = x_axis_rotation.cos();
-                                let sin_phi = x_axis_rotation.sin();
-
-                                let x1 = cos_phi * dx + sin_phi * dy;
-                                let y1 = -sin_phi * dx + cos_phi * dy;
-
-                                let lambda = (x1 * x1) / (rx * rx) + (y1 * y1) / (ry * ry);
-                                let (rx, ry) = if lambda > 1.0 {
-                                    let sqrt_lambda = lambda.sqrt();
-                                    (rx * sqrt_lambda, ry * sqrt_lambda)
-                                } else {
-                                    (rx, ry)
-                                };
-
-                                let sign = if large_arc == sweep { -1.0 } else { 1.0 };
-                                let sq = ((rx * rx * ry * ry) - (rx * rx * y1 * y1) - (ry * ry * x1 * x1)) /
-                                        ((rx * rx * y1 * y1) + (ry * ry * x1 * x1));
-                                let sq = if sq < 0.0 { 0.0 } else { sq };
-                                let coef = sign * sq.sqrt();
-
-                                let cx1 = coef * ((rx * y1) / ry);
-                                let cy1 = coef * -((ry * x1) / rx);
-
-                                let cx = cos_phi * cx1 - sin_phi * cy1 + (current_pos.x + end_point.x) / 2.0;
-                                let cy = sin_phi * cx1 + cos_phi * cy1 + (current_pos.y + end_point.y) / 2.0;
-
-                                let start_angle = ((y1 - cy1) / ry).atan2((x1 - cx1) / rx);
-                                let mut delta_angle = (((-y1 - cy1) / ry).atan2((-x1 - cx1) / rx) - start_angle) % (2.0 * std::f64::consts::PI);
-
-                                if !sweep && delta_angle > 0.0 {
-                                    delta_angle -= 2.0 * std::f64::consts::PI;
-                                } else if sweep && delta_angle < 0.0 {
-                                    delta_angle += 2.0 * std::f64::consts::PI;
-                                }
-
-                                let n_curves = (delta_angle.abs() * 2.0 / std::f64::consts::PI).ceil() as i32;
-                                let delta_angle = delta_angle / n_curves as f64;
-
-                                for i in 0..n_curves {
-                                    let angle = start_angle + i as f64 * delta_angle;
-                                    let next_angle = angle + delta_angle;
-
-                                    let alpha = delta_angle.sin() * (delta_angle.cos() - 1.0) / delta_angle.cos();
-
-                                    let p0 = Point::new(
-                                        cx + (angle.cos() * rx * cos_phi - angle.sin() * ry * sin_phi),
-                                        cy + (angle.cos() * rx * sin_phi + angle.sin() * ry * cos_phi)
-                                    );
-                                    let p3 = Point::new(
-                                        cx + (next_angle.cos() * rx * cos_phi - next_angle.sin() * ry * sin_phi),
-                                        cy + (next_angle.cos() * rx * sin_phi + next_angle.sin() * ry * cos_phi)
-                                    );
-
-                                    let control = Point::new(
-                                        p0.x + alpha * (-angle.sin() * rx * cos_phi - angle.cos() * ry * sin_phi),
-                                        p0.y + alpha * (-angle.sin() * rx * sin_phi + angle.cos() * ry * cos_phi)
-                                    );
-
-                                    let transformed_control = path_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(control, t))
-                                        .unwrap_or(control);
-                                    let transformed_control = group_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(transformed_control, t))
-                                        .unwrap_or(transformed_control);
-
-                                    let transformed_p3 = path_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(p3, t))
-                                        .unwrap_or(p3);
-                                    let transformed_p3 = group_transform
-                                        .as_ref()
-                                        .map(|t| apply_transform(transformed_p3, t))
-                                        .unwrap_or(transformed_p3);
-
-                                    current_shape.records.push(ShapeRecord::Edge(
-                                        shape_records::Edge {
-                                            delta: point_to_vec2d(current_pos, transformed_p3),
-                                            control_delta: Some(point_to_vec2d(current_pos, transformed_control)),
-                                        },
-                                    ));
-
-                                    current_pos = transformed_p3;
-                                }
-                            },
-                        }
-                    }
-
-                    println!("Path processed: {} straight edges, {} curves", path_count, path_count);
-                }
+        let svg = SvgFile::load(&source_path)?;
+
+        // Replace each specified shape ID with the whole drawing or its SVG element
+        for target in &source.shapes {
+            let shape_id = target.shape().resolve(movie, ids.aliases())?;
+            let shape = svg.shape(target.svg_id(), source.tolerance)?;
+            if ids.is_new(shape_id) {
+                new_shapes.push((shape_id, shape));
+            } else {
+                replace_shape_in_movie(movie, shape_id, &shape)?;
             }
-            _ => {}
         }
     }
+    Ok(new_shapes)
+}
 
-    // Add the final shape to the collection
-    if !current_shape.records.is_empty() {
-        let record_count = current_shape.records.len();
-        shapes.push(current_shape);
-        println!("Final shape added to collection with {} records", record_count);
-    }
-
-    Ok(shapes)
+/// Whether any style set of `shape`, the initial one or one started by a record, has a fill.
+fn has_fills(shape: &Shape) -> bool {
+    !shape.initial_styles.fill.is_empty()
+        || shape.records.iter().any(|record| {
+            matches!(record, ShapeRecord::StyleChange(change) if change.new_styles.as_ref().is_some_and(|styles| !styles.fill.is_empty()))
+        })
 }
 
-fn replace_shape_in_movie(movie: &mut Movie, shape_id: u16, new_shapes: &[Shape]) -> Result<(), String> {
+fn replace_shape_in_movie(movie: &mut Movie, shape_id: u16, new_shape: &SvgShape) -> Result<()> {
     println!("Attempting to replace shape ID: {}", shape_id);
-    println!("Number of new shapes available: {}", new_shapes.len());
 
-    // Find the shape tag with matching ID
-    for tag in &mut movie.tags {
+    // Find the shape tag with matching ID, in any timeline
+    let mut replaced = false;
+    let mut needs_shape4 = false;
+    walk_tags_mut(&mut movie.tags, &mut |_, tag| {
+        if replaced {
+            return Ok(());
+        }
         if let Tag::DefineShape(tag) = tag {
             if tag.id == shape_id {
                 println!("Found shape with ID {}", shape_id);
                 println!("Original shape records: {}", tag.shape.records.len());
                 println!("Original fill styles: {}", tag.shape.initial_styles.fill.len());
 
-                // Find a matching shape from the new shapes
-                if let Some(new_shape) = new_shapes.first() {
-                    println!("New shape records: {}", new_shape.records.len());
-                    println!("New fill styles: {}", new_shape.initial_styles.fill.len());
-
-                    // Create a new shape with the original bitmap fills
-                    let mut modified_shape = new_shape.clone();
-
-                    // If the new shape has no fills and the original has bitmap fills, preserve them
-                    if modified_shape.initial_styles.fill.is_empty() && !tag.shape.initial_styles.fill.is_empty() {
-                        // Keep the original bitmap fills
-                        modified_shape.initial_styles.fill = tag.shape.initial_styles.fill.clone();
-
-                        // Update all shape records to use the first bitmap fill
-                        for record in &mut modified_shape.records {
-                            if let ShapeRecord::StyleChange(change) = record {
-                                // Set left_fill to 1 to use the first bitmap fill
-                                change.left_fill = Some(1);
-                                change.right_fill = None;
+                println!("New shape records: {}", new_shape.shape.records.len());
+                println!("New fill styles: {}", new_shape.shape.initial_styles.fill.len());
+
+                // Create a new shape with the original bitmap fills
+                let mut modified_shape = new_shape.shape.clone();
+
+                // If the new shape has no fills and the original has bitmap fills, preserve them
+                if !has_fills(&modified_shape) && !tag.shape.initial_styles.fill.is_empty() {
+                    // Keep the original bitmap fills, in every style set since each path starts a new one
+                    let original_fills = &tag.shape.initial_styles.fill;
+                    modified_shape.initial_styles.fill = original_fills.clone();
+
+                    // Update all shape records to use the first bitmap fill
+                    for record in &mut modified_shape.records {
+                        if let ShapeRecord::StyleChange(change) = record {
+                            if let Some(styles) = &mut change.new_styles {
+                                styles.fill = original_fills.clone();
                             }
+                            // Set left_fill to 1 to use the first bitmap fill
+                            change.left_fill = Some(1);
+                            change.right_fill = None;
                         }
                     }
+                }
 
-                    // Calculate new bounds before assigning
-                    let new_bounds = calculate_shape_bounds(&modified_shape)?;
-                    println!("New shape bounds: {:?}", new_bounds);
-
-                    // Update the shape and bounds
-                    tag.shape = modified_shape;
-                    tag.bounds = new_bounds;
+                // Calculate new bounds before assigning
+                let new_bounds = calculate_shape_bounds(&modified_shape)?;
+                println!("New shape bounds: {:?}", new_bounds);
 
-                    return Ok(());
+                // Update the shape and bounds; edge bounds, which make the tag a DefineShape4,
+                // are kept for shapes that were one and set for styles that need one
+                needs_shape4 = new_shape.fill_winding || needs_define_shape4(&modified_shape);
+                if needs_shape4 || tag.edge_bounds.is_some() {
+                    tag.edge_bounds = Some(new_bounds.clone());
                 }
+                tag.has_fill_winding = new_shape.fill_winding;
+                tag.shape = modified_shape;
+                tag.bounds = new_bounds;
+
+                replaced = true;
             }
         }
+        Ok(())
+    })?;
+    if replaced {
+        if needs_shape4 {
+            require_swf8(movie, "DefineShape4");
+        }
+        return Ok(());
     }
-    Err(format!("Shape with ID {} not found", shape_id))
+    Err(StarDeltaError::missing("DefineShape", shape_id))
 }
 
-fn calculate_shape_bounds(shape: &Shape) -> Result<Rect, String> {
+/// Raises the movie to SWF 8, the first version with `DefineShape4` and `DefineFont3`.
+fn require_swf8(movie: &mut Movie, tag: &str) {
+    if movie.header.swf_version < 8 {
+        println!("Raising SWF version to 8 for {}", tag);
+        movie.header.swf_version = 8;
+    }
+}
+
+fn calculate_shape_bounds(shape: &Shape) -> Result<Rect> {
     let mut min_x = i32::MAX;
     let mut max_x = i32::MIN;
     let mut min_y = i32::MAX;
@@ -1123,101 +728,234 @@ fn calculate_shape_bounds(shape: &Shape) -> Result<Rect, String> {
     })
 }
 
-fn find_next_available_id(movie: &Movie) -> u16 {
-    let mut max_id = 0;
-    for tag in &movie.tags {
-        match tag {
-            Tag::DefineShape(shape) => max_id = max_id.max(shape.id),
-            Tag::DefineSprite(sprite) => max_id = max_id.max(sprite.id),
-            Tag::DefineText(text) => max_id = max_id.max(text.id),
-            Tag::DefineDynamicText(text) => max_id = max_id.max(text.id),
-            Tag::DefineButton(button) => max_id = max_id.max(button.id),
-            Tag::DefineMorphShape(shape) => max_id = max_id.max(shape.id),
-            Tag::DefineBitmap(bitmap) => max_id = max_id.max(bitmap.id),
-            _ => {}
+/// Reserves every explicit ID in `elements`, including characters defined inside new
+/// sprites and button states, so none of them can be handed out automatically.
+fn reserve_new_element_ids(ids: &mut IdAllocator, elements: &NewElements, location: &str) -> Result<()> {
+    for (i, shape) in elements.shapes.iter().flatten().enumerate() {
+        if let Some(id) = shape.id {
+            ids.reserve(id, &format!("{}.shapes[{}]", location, i))?;
+        }
+    }
+    for (i, sprite) in elements.sprites.iter().flatten().enumerate() {
+        let sprite_location = format!("{}.sprites[{}]", location, i);
+        if let Some(id) = sprite.id {
+            ids.reserve(id, &sprite_location)?;
+        }
+        for (j, tag) in sprite.tags.iter().enumerate() {
+            let defines = tag.get("type").and_then(|t| t.as_str()).is_some_and(|t| t.starts_with("Define"));
+            if let Some(id) = value_id(tag).filter(|_| defines) {
+                ids.reserve(id, &format!("{}.tags[{}]", sprite_location, j))?;
+            }
+        }
+    }
+    for (i, text) in elements.texts.iter().flatten().enumerate() {
+        if let Some(id) = text.id {
+            ids.reserve(id, &format!("{}.texts[{}]", location, i))?;
+        }
+    }
+    for (i, bitmap) in elements.bitmaps.iter().flatten().enumerate() {
+        if let Some(id) = bitmap.id {
+            ids.reserve(id, &format!("{}.bitmaps[{}]", location, i))?;
+        }
+    }
+    for (i, button) in elements.buttons.iter().flatten().enumerate() {
+        for (j, state) in button.states.iter().enumerate() {
+            if let Some(id) = value_id(state) {
+                ids.reserve(id, &format!("{}.buttons[{}].states[{}]", location, i, j))?;
+            }
+        }
+    }
+    for (i, font) in elements.fonts.iter().flatten().enumerate() {
+        if let Some(id) = font.id {
+            ids.reserve(id, &format!("{}.fonts[{}]", location, i))?;
+        }
+    }
+    Ok(())
+}
+
+/// Local ID of `name` if the movie already imports it from `url`.
+fn existing_import(movie: &Movie, url: &str, name: &str) -> Option<u16> {
+    movie.tags.iter().find_map(|tag| match tag {
+        Tag::ImportAssets(import) if import.url == url => {
+            import.assets.iter().find(|a| a.name == name).map(|a| a.id)
+        }
+        _ => None,
+    })
+}
+
+/// Reserves the explicit IDs of new imports. Assets already imported from the same library
+/// reuse their ID, which must match an explicit one.
+fn reserve_import_ids(ids: &mut IdAllocator, movie: &Movie, elements: &NewElements, location: &str) -> Result<()> {
+    for (i, import) in elements.imports.iter().flatten().enumerate() {
+        for (j, asset) in import.assets.iter().enumerate() {
+            let asset_location = format!("{}.imports[{}].assets[{}]", location, i, j);
+            match (existing_import(movie, &import.url, &asset.name), asset.id) {
+                (Some(existing), Some(id)) if existing != id => {
+                    return Err(StarDeltaError::config(format!(
+                        "{}: '{}' is already imported from '{}' as ID {}",
+                        asset_location, asset.name, import.url, existing
+                    )));
+                }
+                (None, Some(id)) => ids.reserve(id, &asset_location)?,
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Numeric `id` of a tag given as JSON in the config.
+fn value_id(tag: &serde_json::Value) -> Option<u16> {
+    tag.get("id")?.as_u64()?.try_into().ok()
+}
+
+/// Picks the ID of every new element in both `new_elements` blocks, in the order they are
+/// applied, after reserving all explicit IDs so an auto-assigned one can't take them.
+pub(crate) fn plan_new_element_ids(movie: &Movie, config: &ModificationConfig) -> Result<IdAllocator> {
+    let blocks = [("swf.new_elements", &config.swf.new_elements), ("new_elements", &config.new_elements)];
+    let mut ids = IdAllocator::new(movie);
+    for (location, elements) in blocks {
+        if let Some(elements) = elements {
+            reserve_new_element_ids(&mut ids, elements, location)?;
+            reserve_import_ids(&mut ids, movie, elements, location)?;
+        }
+    }
+    for (location, elements) in blocks {
+        let Some(elements) = elements else { continue };
+        for (i, shape) in elements.shapes.iter().flatten().enumerate() {
+            let element = format!("{}.shapes[{}]", location, i);
+            ids.assign(shape.id, element, "DefineShapeTag", shape.alias.as_deref())?;
+        }
+        for (i, sprite) in elements.sprites.iter().flatten().enumerate() {
+            let element = format!("{}.sprites[{}]", location, i);
+            ids.assign(sprite.id, element, "DefineSpriteTag", sprite.alias.as_deref())?;
+        }
+        for (i, text) in elements.texts.iter().flatten().enumerate() {
+            let element = format!("{}.texts[{}]", location, i);
+            ids.assign(text.id, element, "DefineDynamicTextTag", text.alias.as_deref())?;
+        }
+        for (i, bitmap) in elements.bitmaps.iter().flatten().enumerate() {
+            let element = format!("{}.bitmaps[{}]", location, i);
+            ids.assign(bitmap.id, element, "DefineBitmapTag", bitmap.alias.as_deref())?;
+        }
+        for (i, font) in elements.fonts.iter().flatten().enumerate() {
+            let element = format!("{}.fonts[{}]", location, i);
+            ids.assign(font.id, element, "DefineFontTag", font.alias.as_deref())?;
+        }
+        for (i, import) in elements.imports.iter().flatten().enumerate() {
+            for (j, asset) in import.assets.iter().enumerate() {
+                let element = format!("{}.imports[{}].assets[{}]", location, i, j);
+                // An asset the movie already imports keeps its ID
+                let id = existing_import(movie, &import.url, &asset.name).or(asset.id);
+                ids.assign(id, element, "ImportAssetsTag", asset.alias.as_deref())?;
+            }
         }
     }
-    max_id + 1
+    Ok(ids)
+}
+
+/// Reads swf-types values given as JSON in the config (sprite tags, button states) once
+/// their `"@alias"` IDs can be replaced.
+fn read_config_tags<T: serde::de::DeserializeOwned>(
+    values: &[serde_json::Value],
+    aliases: &Aliases,
+    location: &str,
+) -> Result<Vec<T>> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let mut value = value.clone();
+            aliases.substitute(&mut value)?;
+            serde_path_to_error::deserialize(value)
+                .map_err(|e| StarDeltaError::config(format!("{}[{}]: {}", location, i, e)))
+        })
+        .collect()
 }
 
-fn add_new_shapes(movie: &mut Movie, shapes: &[NewShape], config_path: &Path) -> Result<(), String> {
+fn add_new_shapes(
+    movie: &mut Movie,
+    shapes: &[NewShape],
+    config_dir: &Path,
+    ids: &IdAllocator,
+    location: &str,
+) -> Result<()> {
     println!("Adding new shapes to movie...");
 
-    for shape in shapes {
+    for (i, shape) in shapes.iter().enumerate() {
         // Resolve the source path relative to the config file's directory
-        let source_path = config_path
-            .parent()
-            .ok_or_else(|| "Could not determine config file directory".to_string())?
-            .join(&shape.source);
+        let source_path = config_dir.join(&shape.source);
 
         println!("Processing new shape from source: {}", source_path.display());
 
-        // Parse the SVG source into shapes
-        let parsed_shapes = parse_shape_source(&source_path)?;
-
-        if parsed_shapes.is_empty() {
-            return Err(format!("No shapes found in SVG file: {}", source_path.display()));
-        }
+        // Parse the SVG source, or just its `svg_id` element, into a shape
+        let parsed_shape = SvgFile::load(&source_path)?.shape(shape.svg_id.as_deref(), shape.tolerance)?;
 
-        // Use provided ID or generate a new one
-        let shape_id = shape.id.unwrap_or_else(|| find_next_available_id(movie));
+        let shape_location = format!("{}.shapes[{}]", location, i);
+        let shape_id = ids.planned(&shape_location)?;
 
         // Create the shape tag
+        let bounds = if let Some(bounds) = &shape.bounds {
+            Rect {
+                x_min: bounds.x.min,
+                x_max: bounds.x.max,
+                y_min: bounds.y.min,
+                y_max: bounds.y.max,
+            }
+        } else {
+            calculate_shape_bounds(&parsed_shape.shape)?
+        };
+        // Edge bounds make the tag a DefineShape4, for nonzero fills, focal gradients and gradient strokes
+        let needs_shape4 = parsed_shape.fill_winding || needs_define_shape4(&parsed_shape.shape);
+        if needs_shape4 {
+            require_swf8(movie, "DefineShape4");
+        }
         let shape_tag = Tag::DefineShape(tags::DefineShape {
             id: shape_id,
-            bounds: if let Some(bounds) = &shape.bounds {
-                Rect {
-                    x_min: bounds.x.min,
-                    x_max: bounds.x.max,
-                    y_min: bounds.y.min,
-                    y_max: bounds.y.max,
-                }
-            } else {
-                calculate_shape_bounds(&parsed_shapes[0])?
-            },
-            edge_bounds: None,
-            has_fill_winding: false,
+            edge_bounds: needs_shape4.then(|| bounds.clone()),
+            bounds,
+            has_fill_winding: parsed_shape.fill_winding,
             has_non_scaling_strokes: false,
             has_scaling_strokes: false,
-            shape: parsed_shapes[0].clone(),
+            shape: parsed_shape.shape,
         });
 
         // Add the new shape tag to the movie
-        movie.tags.push(shape_tag);
+        insert_new_definition(movie, shape_tag, shape.before.as_ref(), &shape_location)?;
         println!("Added new shape with ID: {}", shape_id);
     }
 
     Ok(())
 }
 
-fn add_new_sprites(movie: &mut Movie, sprites: &[NewSprite]) -> Result<(), String> {
+fn add_new_sprites(movie: &mut Movie, sprites: &[NewSprite], ids: &IdAllocator, location: &str) -> Result<()> {
     println!("Adding new sprites to movie...");
 
-    for sprite in sprites {
-        // Use provided ID or generate a new one
-        let sprite_id = sprite.id.unwrap_or_else(|| find_next_available_id(movie));
+    for (i, sprite) in sprites.iter().enumerate() {
+        let sprite_location = format!("{}.sprites[{}]", location, i);
+        let sprite_id = ids.planned(&sprite_location)?;
 
         // Create the sprite tag
         let sprite_tag = Tag::DefineSprite(swf_types::tags::DefineSprite {
             id: sprite_id,
             frame_count: sprite.frame_count as usize,
-            tags: sprite.tags.clone(),
+            tags: read_config_tags(&sprite.tags, ids.aliases(), &format!("{}.tags", sprite_location))?,
         });
 
         // Add the new sprite tag to the movie
-        movie.tags.push(sprite_tag);
+        insert_new_definition(movie, sprite_tag, sprite.before.as_ref(), &sprite_location)?;
         println!("Added new sprite with ID: {}", sprite_id);
     }
 
     Ok(())
 }
 
-fn add_new_texts(movie: &mut Movie, texts: &[NewText]) -> Result<(), String> {
+fn add_new_texts(movie: &mut Movie, texts: &[NewText], ids: &IdAllocator, location: &str) -> Result<()> {
     println!("Adding new text elements to movie...");
 
-    for text in texts {
-        // Use provided ID or generate a new one
-        let text_id = text.id.unwrap_or_else(|| find_next_available_id(movie));
+    for (i, text) in texts.iter().enumerate() {
+        let text_location = format!("{}.texts[{}]", location, i);
+        let text_id = ids.planned(&text_location)?;
 
         // Create text bounds
         let bounds = Rect {
@@ -1227,46 +965,268 @@ fn add_new_texts(movie: &mut Movie, texts: &[NewText]) -> Result<(), String> {
             y_max: text.bounds.y.max,
         };
 
+        if text.font_id.is_some() && text.font_class.is_some() {
+            return Err(StarDeltaError::config(format!(
+                "{}: set either font_id or font_class, not both",
+                text_location
+            )));
+        }
+        let font_id = text.font_id.as_ref().map(|font| font.resolve(movie, ids.aliases())).transpose()?;
+
         // Create a dynamic text tag
         let text_tag = Tag::DefineDynamicText(swf_types::tags::DefineDynamicText {
             id: text_id,
             bounds,
             word_wrap: text.word_wrap,
             multiline: text.multiline,
-            password: false,
+            password: text.password,
             readonly: text.readonly,
-            auto_size: false,
+            auto_size: text.auto_size,
             no_select: text.no_select,
-            border: false,
+            border: text.border,
             was_static: false,
             html: text.html,
             use_glyph_font: text.use_outlines,
-            font_id: None,
-            font_class: Some(text.font_class.clone()),
+            font_id,
+            font_class: text.font_class.clone(),
             font_size: Some(text.font_size),
             color: text.color,
-            max_length: None,
+            max_length: text.max_length.map(Into::into),
             align: text.align,
             margin_left: text.margin_left,
             margin_right: text.margin_right,
             indent: text.indent,
             leading: text.leading,
-            variable_name: None,
+            variable_name: text.variable_name.clone(),
             text: Some(text.text.clone()),
         });
 
         // Add the new text tag to the movie
-        movie.tags.push(text_tag);
+        insert_new_definition(movie, text_tag, text.before.as_ref(), &text_location)?;
         println!("Added new text with ID: {}", text_id);
     }
 
     Ok(())
 }
 
-fn add_new_elements(movie: &mut Movie, elements: &NewElements) -> Result<(), String> {
+/// Adds the assets of each import to the movie's `ImportAssets` tag for the same library,
+/// or to a new `ImportAssets2` tag placed before anything can use them.
+fn add_new_imports(movie: &mut Movie, imports: &[NewImport], ids: &IdAllocator, location: &str) -> Result<()> {
+    println!("Adding new imports to movie...");
+
+    for (i, import) in imports.iter().enumerate() {
+        let import_location = format!("{}.imports[{}]", location, i);
+        let mut assets = Vec::new();
+        for (j, asset) in import.assets.iter().enumerate() {
+            let id = ids.planned(&format!("{}.assets[{}]", import_location, j))?;
+            if existing_import(movie, &import.url, &asset.name).is_none() {
+                assets.push(swf_types::NamedId {
+                    id,
+                    name: asset.name.clone(),
+                });
+            }
+            println!("Imported '{}' from '{}' as ID {}", asset.name, import.url, id);
+        }
+
+        let existing = movie.tags.iter_mut().find_map(|tag| match tag {
+            Tag::ImportAssets(existing) if existing.url == import.url => Some(existing),
+            _ => None,
+        });
+        match existing {
+            Some(existing) => existing.assets.extend(assets),
+            None => {
+                let tag = Tag::ImportAssets(tags::ImportAssets {
+                    url: import.url.clone(),
+                    assets,
+                });
+                insert_import(&mut movie.tags, tag, import.before.as_ref())
+                    .map_err(|e| StarDeltaError::config(format!("{}.before: {}", import_location, e)))?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds each font of `fonts` without glyphs, so texts can bind to it by ID or name.
+/// [`embed_new_fonts`] embeds the glyphs once every text is final.
+fn add_new_fonts(
+    movie: &mut Movie,
+    fonts: &[NewFont],
+    config_dir: &Path,
+    ids: &IdAllocator,
+    location: &str,
+) -> Result<()> {
+    println!("Adding new fonts to movie...");
+
+    for (i, font) in fonts.iter().enumerate() {
+        let font_location = format!("{}.fonts[{}]", location, i);
+        let font_id = ids.planned(&font_location)?;
+        if font.chars.is_empty() && !font.used_chars {
+            return Err(StarDeltaError::config(format!(
+                "{}: set chars or used_chars to choose the glyphs to embed",
+                font_location
+            )));
+        }
+
+        let source_path = config_dir.join(&font.source);
+        println!("Processing new font from source: {}", source_path.display());
+        let file = FontFile::load(&source_path)?;
+        let embedded = embed_font(&file, font_id, font, &BTreeSet::new())?;
+
+        require_swf8(movie, "DefineFont3");
+        insert_new_definition(movie, Tag::DefineFont(embedded.font), font.before.as_ref(), &font_location)?;
+        let font_index = movie
+            .tags
+            .iter()
+            .position(|t| matches!(t, Tag::DefineFont(f) if f.id == font_id))
+            .ok_or_else(|| StarDeltaError::missing("DefineFontTag", font_id))?;
+        movie.tags.insert(font_index + 1, Tag::DefineFontAlignZones(embedded.zones));
+        println!("Added new font with ID: {}", font_id);
+    }
+
+    Ok(())
+}
+
+fn embed_font(file: &FontFile, font_id: u16, font: &NewFont, chars: &BTreeSet<char>) -> Result<EmbeddedFont> {
+    let name = match &font.name {
+        Some(name) => name.clone(),
+        None => file.family_name()?,
+    };
+    let embedded = file.embed(font_id, &name, font.bold, font.italic, chars)?;
+    if !embedded.missing.is_empty() {
+        let missing: String = embedded.missing.iter().collect();
+        println!("Warning: font '{}' has no glyphs for {:?}", name, missing);
+    }
+    Ok(embedded)
+}
+
+/// Embeds the glyphs of every new font: its `chars`, plus with `used_chars` the characters
+/// shown by the texts bound to it, now that texts are added and modified.
+fn embed_new_fonts(movie: &mut Movie, config: &ModificationConfig, ids: &IdAllocator, config_dir: &Path) -> Result<()> {
+    let blocks = [("swf.new_elements", &config.swf.new_elements), ("new_elements", &config.new_elements)];
+    for (location, elements) in blocks {
+        let Some(elements) = elements else { continue };
+        for (i, font) in elements.fonts.iter().flatten().enumerate() {
+            let font_location = format!("{}.fonts[{}]", location, i);
+            let font_id = ids.planned(&font_location)?;
+            let file = FontFile::load(&config_dir.join(&font.source))?;
+            let mut chars = parse_char_set(&font.chars, &format!("{}.chars", font_location))?;
+            if font.used_chars {
+                chars.extend(used_chars(movie, font_id));
+            }
+            let embedded = embed_font(&file, font_id, font, &chars)?;
+
+            for tag in &mut movie.tags {
+                match tag {
+                    Tag::DefineFont(f) if f.id == font_id => *f = embedded.font.clone(),
+                    Tag::DefineFontAlignZones(z) if z.font_id == font_id => *z = embedded.zones.clone(),
+                    _ => {}
+                }
+            }
+            println!("Embedded {} characters in font {}", chars.len() - embedded.missing.len(), font_id);
+        }
+    }
+    Ok(())
+}
+
+/// Config location, placement and insertion anchor of every new element that defines a
+/// character with a planned ID.
+pub(crate) fn new_element_locations<'a>(
+    elements: &'a NewElements,
+    location: &str,
+) -> Vec<(String, Option<&'a Placement>, Option<&'a Anchor>)> {
+    let mut locations = Vec::new();
+    for (i, shape) in elements.shapes.iter().flatten().enumerate() {
+        locations.push((format!("{}.shapes[{}]", location, i), shape.place.as_ref(), shape.before.as_ref()));
+    }
+    for (i, sprite) in elements.sprites.iter().flatten().enumerate() {
+        locations.push((format!("{}.sprites[{}]", location, i), sprite.place.as_ref(), sprite.before.as_ref()));
+    }
+    for (i, text) in elements.texts.iter().flatten().enumerate() {
+        locations.push((format!("{}.texts[{}]", location, i), text.place.as_ref(), text.before.as_ref()));
+    }
+    for (i, font) in elements.fonts.iter().flatten().enumerate() {
+        locations.push((format!("{}.fonts[{}]", location, i), None, font.before.as_ref()));
+    }
+    for (i, bitmap) in elements.bitmaps.iter().flatten().enumerate() {
+        locations.push((format!("{}.bitmaps[{}]", location, i), None, bitmap.before.as_ref()));
+    }
+    locations
+}
+
+/// Inserts the definition of the new element at `location`, see [`insert_definition`].
+fn insert_new_definition(movie: &mut Movie, tag: Tag, before: Option<&Anchor>, location: &str) -> Result<()> {
+    insert_definition(&mut movie.tags, tag, before)
+        .map_err(|e| StarDeltaError::config(format!("{}.before: {}", location, e)))
+}
+
+/// Adds a `PlaceObject` for character `id` as described by `place`.
+fn place_new_character(movie: &mut Movie, id: u16, place: &Placement, aliases: &Aliases, location: &str) -> Result<()> {
+    let place_object = tags::PlaceObject {
+        is_update: false,
+        depth: place.depth,
+        character_id: Some(id),
+        class_name: None,
+        matrix: place.matrix.clone(),
+        color_transform: place.color_transform.clone(),
+        ratio: None,
+        name: place.name.clone(),
+        clip_depth: None,
+        filters: None,
+        blend_mode: None,
+        bitmap_cache: None,
+        // Only PlaceObject3 can carry this flag, so the tag is written as PlaceObject3
+        visible: Some(true),
+        background_color: None,
+        clip_actions: None,
+    };
+    let timeline = match &place.sprite {
+        Some(sprite) => {
+            let sprite_id = sprite.resolve(movie, aliases)?;
+            &mut find_sprite_mut(&mut movie.tags, sprite_id)
+                .ok_or_else(|| StarDeltaError::missing("DefineSpriteTag", sprite_id))?
+                .tags
+        }
+        None => &mut movie.tags,
+    };
+    insert_place_object(timeline, place.frame, place_object)
+        .map_err(|e| StarDeltaError::config(format!("{}.place: {}", location, e)))?;
+    println!("Placed character {} at depth {}, frame {}", id, place.depth, place.frame);
+    Ok(())
+}
+
+/// Adds every element of the `new_elements` block found at `location` in the config, with
+/// the IDs picked by [`plan_new_element_ids`], places the ones with a `place` block and
+/// moves each new definition in front of its first use.
+fn add_new_elements(
+    movie: &mut Movie,
+    elements: &NewElements,
+    location: &str,
+    ids: &IdAllocator,
+    config_dir: &Path,
+) -> Result<()> {
+    // Imports and fonts go first so texts can select fonts by name
+    if let Some(imports) = &elements.imports {
+        add_new_imports(movie, imports, ids, location)?;
+    }
+    if let Some(fonts) = &elements.fonts {
+        add_new_fonts(movie, fonts, config_dir, ids, location)?;
+    }
+    if let Some(shapes) = &elements.shapes {
+        add_new_shapes(movie, shapes, config_dir, ids, location)?;
+    }
+    if let Some(sprites) = &elements.sprites {
+        add_new_sprites(movie, sprites, ids, location)?;
+    }
+    if let Some(texts) = &elements.texts {
+        add_new_texts(movie, texts, ids, location)?;
+    }
+
     if let Some(bitmaps) = &elements.bitmaps {
-        for bitmap in bitmaps {
-            let bitmap_id = bitmap.id.unwrap_or_else(|| find_next_available_id(movie));
+        for (i, bitmap) in bitmaps.iter().enumerate() {
+            let bitmap_location = format!("{}.bitmaps[{}]", location, i);
+            let bitmap_id = ids.planned(&bitmap_location)?;
             let bitmap_tag = Tag::DefineBitmap(swf_types::tags::DefineBitmap {
                 id: bitmap_id,
                 width: bitmap.width,
@@ -1274,19 +1234,38 @@ fn add_new_elements(movie: &mut Movie, elements: &NewElements) -> Result<(), Str
                 media_type: swf_types::ImageType::Png,  // Use the re-exported ImageType
                 data: bitmap.data.clone(),
             });
-            movie.tags.push(bitmap_tag);
+            insert_new_definition(movie, bitmap_tag, bitmap.before.as_ref(), &bitmap_location)?;
         }
     }
 
     if let Some(buttons) = &elements.buttons {
-        for button in buttons {
-            for state in &button.states {
-                let button_tag = Tag::DefineButton(state.clone());
-                movie.tags.push(button_tag);
+        for (i, button) in buttons.iter().enumerate() {
+            let button_location = format!("{}.buttons[{}]", location, i);
+            let states: Vec<tags::DefineButton> =
+                read_config_tags(&button.states, ids.aliases(), &format!("{}.states", button_location))?;
+            for state in states {
+                insert_new_definition(movie, Tag::DefineButton(state), button.before.as_ref(), &button_location)?;
             }
         }
     }
 
+    // Place characters once every new sprite exists, so they can be placed in each other.
+    // Definitions without an explicit anchor then move up to their first use.
+    let mut defined = Vec::new();
+    for (element, place, before) in new_element_locations(elements, location) {
+        let id = ids.planned(&element)?;
+        if let Some(place) = place {
+            place_new_character(movie, id, place, ids.aliases(), &element)?;
+        }
+        if before.is_none() {
+            defined.push(id);
+        }
+    }
+    for button in elements.buttons.iter().flatten().filter(|b| b.before.is_none()) {
+        defined.extend(button.states.iter().filter_map(value_id));
+    }
+    move_before_first_use(&mut movie.tags, &defined)?;
+
     if let Some(scenes) = &elements.scenes {
         // Find or create the scene data tag
         let mut scene_data_tag = None;
@@ -1313,70 +1292,61 @@ fn add_new_elements(movie: &mut Movie, elements: &NewElements) -> Result<(), Str
                 })
                 .collect();
 
-            movie.tags.push(Tag::DefineSceneAndFrameLabelData(
+            let scene_data = Tag::DefineSceneAndFrameLabelData(
                 swf_types::tags::DefineSceneAndFrameLabelData {
                     scenes: new_scenes,
                     labels: Vec::new(),
                 }
-            ));
+            );
+            insert_definition(&mut movie.tags, scene_data, None)?;
         }
     }
 
     Ok(())
 }
 
-fn remove_swf_elements(movie: &mut Movie, elements: &RemoveElements) -> Result<(), String> {
+fn remove_swf_elements(
+    movie: &mut Movie,
+    elements: &RemoveElements,
+    aliases: &Aliases,
+) -> Result<Vec<RemovedCharacter>> {
     println!("Starting element removal process...");
 
-    // Create a set of IDs to remove for each type
-    let shape_ids: std::collections::HashSet<_> = elements.shapes.as_ref().map(|v| v.iter().copied().collect()).unwrap_or_default();
-    let sprite_ids: std::collections::HashSet<_> = elements.sprites.as_ref().map(|v| v.iter().copied().collect()).unwrap_or_default();
-    let text_ids: std::collections::HashSet<_> = elements.texts.as_ref().map(|v| v.iter().copied().collect()).unwrap_or_default();
-    let button_ids: std::collections::HashSet<_> = elements.buttons.as_ref().map(|v| v.iter().copied().collect()).unwrap_or_default();
-    let bitmap_ids: std::collections::HashSet<_> = elements.bitmaps.as_ref().map(|v| v.iter().copied().collect()).unwrap_or_default();
+    // Collect the character IDs to remove, whatever their type
+    let requested: BTreeSet<u16> = [
+        &elements.shapes,
+        &elements.sprites,
+        &elements.texts,
+        &elements.buttons,
+        &elements.bitmaps,
+    ]
+    .into_iter()
+    .flatten()
+    .flatten()
+    .map(|character| character.resolve(movie, aliases))
+    .collect::<Result<_>>()?;
     let frame_labels: std::collections::HashSet<_> = elements.frames.as_ref().map(|v| v.iter().cloned().collect()).unwrap_or_default();
     let scene_names: std::collections::HashSet<_> = elements.scenes.as_ref().map(|v| v.iter().cloned().collect()).unwrap_or_default();
 
-    // First pass: Remove all references to the elements in the main timeline
-    movie.tags.retain(|tag| {
+    // Work out dependents and orphans, then remove the definitions and every reference to them
+    let graph = DependencyGraph::build(movie);
+    let plan = plan_removal(&graph, &requested, elements.cascade)?;
+    let ids: BTreeSet<u16> = plan.keys().copied().collect();
+    remove_characters(movie, &ids);
+
+    // Remove frame labels, in every timeline
+    retain_tags(&mut movie.tags, &mut |_, tag| {
         match tag {
-            Tag::PlaceObject(place) => {
-                let char_id = place.character_id.unwrap_or(0);
-                !shape_ids.contains(&char_id) &&
-                !sprite_ids.contains(&char_id) &&
-                !text_ids.contains(&char_id) &&
-                !button_ids.contains(&char_id) &&
-                !bitmap_ids.contains(&char_id)
-            },
             Tag::FrameLabel(label) => !frame_labels.contains(&label.name),
             _ => true
         }
     });
 
-    // Second pass: Remove the actual element definitions
+    // Drop scene data that would be left empty
     movie.tags.retain(|tag| {
         match tag {
-            Tag::DefineShape(shape) => !shape_ids.contains(&shape.id),
-            Tag::DefineSprite(sprite) => !sprite_ids.contains(&sprite.id),
-            Tag::DefineText(text) => !text_ids.contains(&text.id),
-            Tag::DefineDynamicText(text) => !text_ids.contains(&text.id),
-            Tag::DefineButton(button) => !button_ids.contains(&button.id),
-            Tag::DefineBitmap(bitmap) => !bitmap_ids.contains(&bitmap.id),
-            Tag::DefineSceneAndFrameLabelData(data) => {
-                // For scene data, we'll keep the tag but filter its contents if needed
-                if scene_names.is_empty() {
-                    true
-                } else {
-                    // Create a filtered copy of scenes
-                    let filtered_scenes = data.scenes
-                        .iter()
-                        .filter(|scene| !scene_names.contains(&scene.name))
-                        .cloned()
-                        .collect::<Vec<_>>();
-
-                    // Only keep if we have scenes or labels
-                    !filtered_scenes.is_empty() || !data.labels.is_empty()
-                }
+            Tag::DefineSceneAndFrameLabelData(data) if !scene_names.is_empty() => {
+                data.scenes.iter().any(|scene| !scene_names.contains(&scene.name)) || !data.labels.is_empty()
             },
             _ => true
         }
@@ -1386,40 +1356,36 @@ fn remove_swf_elements(movie: &mut Movie, elements: &RemoveElements) -> Result<(
     if !scene_names.is_empty() {
         for tag in &mut movie.tags {
             if let Tag::DefineSceneAndFrameLabelData(data) = tag {
-                data.scenes = data.scenes
-                    .iter()
-                    .filter(|scene| !scene_names.contains(&scene.name))
-                    .cloned()
-                    .collect();
+                data.scenes.retain(|scene| !scene_names.contains(&scene.name));
             }
         }
     }
 
-    // Clean up any references to removed elements in sprite tags
-    for tag in &mut movie.tags {
-        if let Tag::DefineSprite(sprite) = tag {
-            sprite.tags.retain(|tag| {
-                match tag {
-                    Tag::PlaceObject(place) => {
-                        let char_id = place.character_id.unwrap_or(0);
-                        !shape_ids.contains(&char_id) &&
-                        !sprite_ids.contains(&char_id) &&
-                        !text_ids.contains(&char_id) &&
-                        !button_ids.contains(&char_id) &&
-                        !bitmap_ids.contains(&char_id)
-                    },
-                    Tag::FrameLabel(label) => !frame_labels.contains(&label.name),
-                    _ => true
-                }
-            });
-        }
+    // IDs that were not defined in the movie were not removed
+    let removed: Vec<RemovedCharacter> = plan
+        .into_iter()
+        .filter_map(|(id, reason)| {
+            graph.kind(id).map(|tag| RemovedCharacter {
+                id,
+                tag: tag.to_string(),
+                reason,
+            })
+        })
+        .collect();
+    for character in &removed {
+        println!("Removed {}", character);
     }
 
     println!("Element removal completed successfully");
-    Ok(())
+    Ok(removed)
 }
 
-fn apply_modifications(movie: &mut Movie, config: &SwfModification, config_path: &Path) -> Result<(), String> {
+fn apply_modifications(
+    movie: &mut Movie,
+    config: &SwfModification,
+    ids: &IdAllocator,
+    config_dir: &Path,
+) -> Result<PatchSummary> {
     if let Some(bounds) = &config.bounds {
         movie.header.frame_size.x_min = bounds.x.min;
         movie.header.frame_size.x_max = bounds.x.max;
@@ -1428,50 +1394,432 @@ fn apply_modifications(movie: &mut Movie, config: &SwfModification, config_path:
     }
 
     // Apply existing tag modifications
-    for modification in &config.modifications {
-        apply_tag_modification(movie, modification)?;
+    let mut summary = PatchSummary::default();
+    for (i, modification) in config.modifications.iter().enumerate() {
+        let resolved = match modification.resolve(movie, ids.aliases()) {
+            Ok(resolved) => Some(resolved),
+            Err(e) if modification.optional => {
+                println!("Skipping optional modification {}: {}", i, e);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        let touched = match resolved.as_deref() {
+            Some(m) if is_timeline_tag(&m.tag) => apply_timeline_modification(movie, m, ids.aliases())?,
+            Some(m) if m.mode == ModificationMode::Merge => merge_tag_modification(movie, m)?,
+            Some(m) => apply_tag_modification(movie, m)?,
+            None => 0,
+        };
+        summary.modifications.push(ModificationCount {
+            location: format!("swf.modifications[{}]", i),
+            tag: modification.tag.clone(),
+            id: resolved.as_ref().map_or(modification.id, |m| m.id),
+            optional: modification.optional,
+            touched,
+        });
     }
 
     // Handle new elements if present
     if let Some(new_elements) = &config.new_elements {
-        if let Some(shapes) = &new_elements.shapes {
-            add_new_shapes(movie, shapes, config_path)?;
+        add_new_elements(movie, new_elements, "swf.new_elements", ids, config_dir)?;
+    }
+
+    // Handle element removal if present
+    if let Some(remove_elements) = &config.remove_elements {
+        summary.removed.extend(remove_swf_elements(movie, remove_elements, ids.aliases())?);
+    }
+
+    Ok(summary)
+}
+
+/// Returns the character ID a tag defines, with the tag's name as used in configs.
+pub(crate) fn character_tag(tag: &Tag) -> Option<(u16, &'static str)> {
+    Some(match tag {
+        Tag::DefineBinaryData(t) => (t.id, "DefineBinaryDataTag"),
+        Tag::DefineBitmap(t) => (t.id, "DefineBitmapTag"),
+        Tag::DefineButton(t) => (t.id, "DefineButtonTag"),
+        Tag::DefineCffFont(t) => (t.id, "DefineCffFontTag"),
+        Tag::DefineDynamicText(t) => (t.id, "DefineDynamicTextTag"),
+        Tag::DefineFont(t) => (t.id, "DefineFontTag"),
+        Tag::DefineGlyphFont(t) => (t.id, "DefineGlyphFontTag"),
+        Tag::DefineMorphShape(t) => (t.id, "DefineMorphShapeTag"),
+        Tag::DefineShape(t) => (t.id, "DefineShapeTag"),
+        Tag::DefineSound(t) => (t.id, "DefineSoundTag"),
+        Tag::DefineSprite(t) => (t.id, "DefineSpriteTag"),
+        Tag::DefineText(t) => (t.id, "DefineTextTag"),
+        Tag::DefineVideoStream(t) => (t.id, "DefineVideoStreamTag"),
+        _ => return None,
+    })
+}
+
+/// Config name of any tag: its swf-types `type` with a `Tag` suffix (`"PlaceObjectTag"`).
+/// Tags are told apart by variant, since serializing them copies bytecode and image data.
+pub(crate) fn tag_config_name(tag: &Tag) -> Option<Cow<'static, str>> {
+    if let Some((_, name)) = character_tag(tag) {
+        return Some(Cow::Borrowed(name));
+    }
+    let name = match tag {
+        Tag::CsmTextSettings { .. } => "CsmTextSettingsTag",
+        Tag::DefineButtonColorTransform { .. } => "DefineButtonColorTransformTag",
+        Tag::DefineButtonSound { .. } => "DefineButtonSoundTag",
+        Tag::DefineFontAlignZones { .. } => "DefineFontAlignZonesTag",
+        Tag::DefineFontInfo { .. } => "DefineFontInfoTag",
+        Tag::DefineFontName { .. } => "DefineFontNameTag",
+        Tag::DefineJpegTables { .. } => "DefineJpegTablesTag",
+        Tag::DefineScalingGrid { .. } => "DefineScalingGridTag",
+        Tag::DefineSceneAndFrameLabelData { .. } => "DefineSceneAndFrameLabelDataTag",
+        Tag::DoAbc { .. } => "DoAbcTag",
+        Tag::DoAction { .. } => "DoActionTag",
+        Tag::DoInitAction { .. } => "DoInitActionTag",
+        Tag::EnableDebugger { .. } => "EnableDebuggerTag",
+        Tag::ExportAssets { .. } => "ExportAssetsTag",
+        Tag::FileAttributes { .. } => "FileAttributesTag",
+        Tag::FrameLabel { .. } => "FrameLabelTag",
+        Tag::ImportAssets { .. } => "ImportAssetsTag",
+        Tag::Metadata { .. } => "MetadataTag",
+        Tag::PlaceObject { .. } => "PlaceObjectTag",
+        Tag::Protect { .. } => "ProtectTag",
+        Tag::Raw { .. } => "RawTag",
+        Tag::RemoveObject { .. } => "RemoveObjectTag",
+        Tag::ScriptLimits { .. } => "ScriptLimitsTag",
+        Tag::SetBackgroundColor { .. } => "SetBackgroundColorTag",
+        Tag::SetTabIndex { .. } => "SetTabIndexTag",
+        Tag::ShowFrame { .. } => "ShowFrameTag",
+        Tag::SoundStreamBlock { .. } => "SoundStreamBlockTag",
+        Tag::SoundStreamHead { .. } => "SoundStreamHeadTag",
+        Tag::StartSound { .. } => "StartSoundTag",
+        Tag::StartSound2 { .. } => "StartSound2Tag",
+        Tag::SymbolClass { .. } => "SymbolClassTag",
+        Tag::Telemetry { .. } => "TelemetryTag",
+        Tag::VideoFrame { .. } => "VideoFrameTag",
+        // Rare tags not named above are small, so reading their `type` is cheap enough
+        _ => {
+            let value = serde_json::to_value(tag).ok()?;
+            return value.get("type")?.as_str().map(|t| Cow::Owned(format!("{}Tag", t)));
         }
-        if let Some(sprites) = &new_elements.sprites {
-            add_new_sprites(movie, sprites)?;
+    };
+    Some(Cow::Borrowed(name))
+}
+
+/// Whether a merge-mode modification targets `tag`. Character tags (and button color
+/// transforms) must match `id` too; every other tag of the named type matches.
+pub(crate) fn modification_matches(tag: &Tag, modification: &TagModification) -> bool {
+    match (character_tag(tag), tag) {
+        (Some((id, name)), _) => name == modification.tag && id == modification.id,
+        (None, Tag::DefineButtonColorTransform(t)) => {
+            modification.tag == "DefineButtonColorTransformTag" && t.button_id == modification.id
         }
-        if let Some(texts) = &new_elements.texts {
-            add_new_texts(movie, texts)?;
+        (None, _) => tag_config_name(tag).as_deref() == Some(modification.tag.as_str()),
+    }
+}
+
+/// Applies an RFC 7386 JSON merge patch: objects merge recursively, `null` removes a key
+/// and any other value replaces the target.
+fn merge_patch(target: &mut serde_json::Value, patch: &serde_json::Value) {
+    let serde_json::Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = serde_json::Value::Object(serde_json::Map::new());
+    }
+    if let serde_json::Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(serde_json::Value::Null), value);
+            }
         }
     }
+}
 
-    // Handle element removal if present
-    if let Some(remove_elements) = &config.remove_elements {
-        remove_swf_elements(movie, remove_elements)?;
+/// Splits a merge patch into single-value patches, each with its dotted property path.
+fn merge_patch_leaves(
+    patch: &serde_json::Value,
+    path: &mut Vec<String>,
+    leaves: &mut Vec<(String, serde_json::Value)>,
+) {
+    match patch {
+        serde_json::Value::Object(fields) if !fields.is_empty() => {
+            for (key, value) in fields {
+                path.push(key.clone());
+                merge_patch_leaves(value, path, leaves);
+                path.pop();
+            }
+        }
+        _ => {
+            let leaf = path.iter().rev().fold(patch.clone(), |inner, key| {
+                let mut object = serde_json::Map::new();
+                object.insert(key.clone(), inner);
+                serde_json::Value::Object(object)
+            });
+            leaves.push((path.join("."), leaf));
+        }
     }
+}
 
+/// Serializes `tag`, merges `properties` into it and deserializes the result.
+fn merge_into_tag(tag: &Tag, tag_name: &str, properties: &serde_json::Value) -> Result<Tag> {
+    let original = serde_json::to_value(tag).map_err(|e| StarDeltaError::JsonSerialize { message: e.to_string() })?;
+    let mut merged = original.clone();
+    merge_patch(&mut merged, properties);
+    serde_json::from_value(merged).map_err(|e| {
+        // serde loses the path inside tagged enums, so find the first property that fails on its own
+        let mut leaves = Vec::new();
+        merge_patch_leaves(properties, &mut Vec::new(), &mut leaves);
+        let property = leaves
+            .into_iter()
+            .find(|(_, leaf)| {
+                let mut single = original.clone();
+                merge_patch(&mut single, leaf);
+                serde_json::from_value::<Tag>(single).is_err()
+            })
+            .map(|(path, _)| path)
+            .unwrap_or_else(|| "properties".to_string());
+        StarDeltaError::invalid_property(tag_name, property, e)
+    })
+}
+
+fn check_merge_properties(modification: &TagModification) -> Result<()> {
+    if !modification.properties.is_object() {
+        return Err(StarDeltaError::invalid_property(&modification.tag, "properties", "expected an object"));
+    }
+    if modification.properties.get("type").is_some() {
+        return Err(StarDeltaError::invalid_property(&modification.tag, "type", "the tag type cannot be changed"));
+    }
     Ok(())
 }
 
-fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) -> Result<(), String> {
-    for tag in &mut movie.tags {
+/// Merges `properties` into every matching tag and returns how many tags matched.
+fn merge_tag_modification(movie: &mut Movie, modification: &TagModification) -> Result<usize> {
+    check_merge_properties(modification)?;
+
+    let mut touched = 0;
+    walk_tags_mut(&mut movie.tags, &mut |_, tag| {
+        if modification_matches(tag, modification) {
+            *tag = merge_into_tag(tag, &modification.tag, &modification.properties)?;
+            touched += 1;
+        }
+        Ok(())
+    })?;
+    Ok(touched)
+}
+
+/// Tags that belong to a timeline position rather than a character, see `TimelineScope`.
+pub(crate) fn is_timeline_tag(tag: &str) -> bool {
+    matches!(tag, "PlaceObjectTag" | "RemoveObjectTag" | "FrameLabelTag" | "DoActionTag")
+}
+
+/// A timeline scope with its selectors resolved. Tags must be passed to
+/// [`ScopeWalk::selects`] in timeline order, since later `PlaceObject` and `RemoveObject`
+/// tags may only give the depth of the character they affect.
+pub(crate) struct ScopeWalk {
+    filter: ScopeFilter,
+    start: TagPath,
+    recursive: bool,
+    /// Character placed at each depth, per timeline
+    depths: HashMap<Vec<u16>, HashMap<u16, u16>>,
+}
+
+impl ScopeWalk {
+    pub(crate) fn new(movie: &Movie, scope: Option<&TimelineScope>, aliases: &Aliases) -> Result<Self> {
+        let scope = scope.cloned().unwrap_or_default();
+        let sprite_id = scope.sprite.as_ref().map(|s| s.resolve(movie, aliases)).transpose()?;
+        Ok(ScopeWalk {
+            filter: ScopeFilter {
+                frame: scope.frame,
+                depth: scope.depth,
+                character: scope.character.as_ref().map(|c| c.resolve(movie, aliases)).transpose()?,
+            },
+            start: sprite_id.map_or_else(TagPath::default, TagPath::sprite),
+            recursive: scope.recursive,
+            depths: HashMap::new(),
+        })
+    }
+
+    /// Sprite whose timeline the scope covers, `None` for the root timeline.
+    pub(crate) fn sprite(&self) -> Option<u16> {
+        self.start.sprites.last().copied()
+    }
+
+    /// Whether `tag`, visited at `path`, is within the scope.
+    pub(crate) fn selects(&mut self, path: &TagPath, tag: &Tag) -> bool {
+        if !self.recursive && path.sprites != self.start.sprites {
+            return false;
+        }
+        let (depth, character) = match tag {
+            Tag::PlaceObject(place) => {
+                let depths = self.depths.entry(path.sprites.clone()).or_default();
+                if let Some(id) = place.character_id {
+                    depths.insert(place.depth, id);
+                }
+                (Some(place.depth), depths.get(&place.depth).copied())
+            }
+            Tag::RemoveObject(remove) => {
+                let depths = self.depths.entry(path.sprites.clone()).or_default();
+                let character = depths.remove(&remove.depth);
+                (Some(remove.depth), remove.character_id.or(character))
+            }
+            _ => (None, None),
+        };
+        self.filter.matches(path.frame, depth, character)
+    }
+}
+
+/// Applies a modification of a timeline tag to the tags selected by its scope.
+fn apply_timeline_modification(movie: &mut Movie, modification: &TagModification, aliases: &Aliases) -> Result<usize> {
+    if modification.mode == ModificationMode::Merge {
+        check_merge_properties(modification)?;
+    }
+    let mut scope = ScopeWalk::new(movie, modification.scope.as_ref(), aliases)?;
+    let start = scope.start.clone();
+    let timeline = match scope.sprite() {
+        Some(id) => {
+            &mut find_sprite_mut(&mut movie.tags, id)
+                .ok_or_else(|| StarDeltaError::missing("DefineSpriteTag", id))?
+                .tags
+        }
+        None => &mut movie.tags,
+    };
+
+    let mut touched = 0;
+    walk_tags_mut_at(timeline, start, &mut |path, tag| {
+        if !scope.selects(path, tag) {
+            return Ok(());
+        }
+        let changed = match modification.mode {
+            ModificationMode::Fields => set_timeline_fields(tag, modification)?,
+            ModificationMode::Merge if modification_matches(tag, modification) => {
+                *tag = merge_into_tag(tag, &modification.tag, &modification.properties)?;
+                true
+            }
+            ModificationMode::Merge => false,
+        };
+        if changed {
+            touched += 1;
+        }
+        Ok(())
+    })?;
+    Ok(touched)
+}
+
+/// How many tags a timeline modification would change, counted like
+/// `apply_timeline_modification` without changing them.
+pub(crate) fn count_timeline_matches(movie: &Movie, modification: &TagModification, aliases: &Aliases) -> Result<usize> {
+    let mut scope = ScopeWalk::new(movie, modification.scope.as_ref(), aliases)?;
+    let start = scope.start.clone();
+    let timeline = match scope.sprite() {
+        Some(id) => &find_sprite(&movie.tags, id).ok_or_else(|| StarDeltaError::missing("DefineSpriteTag", id))?.tags,
+        None => &movie.tags,
+    };
+
+    let mut count = 0;
+    walk_tags_at(timeline, start, &mut |path, tag| {
+        let matches = match modification.mode {
+            ModificationMode::Fields => tag_config_name(tag).as_deref() == Some(modification.tag.as_str()),
+            ModificationMode::Merge => modification_matches(tag, modification),
+        };
+        // Every tag goes through the scope, so depths are tracked even for other tag types
+        if scope.selects(path, tag) && matches {
+            count += 1;
+        }
+    });
+    Ok(count)
+}
+
+/// Sets the supported fields of a timeline tag. Returns `false` if `tag` is not the
+/// modification's tag type.
+fn set_timeline_fields(tag: &mut Tag, modification: &TagModification) -> Result<bool> {
+    match (tag, modification.tag.as_str()) {
+        (Tag::DoAction(tag), "DoActionTag") => {
+            if let Some(actions) = modification.properties.get("actions") {
+                tag.actions = serde_json::from_value(actions.clone())
+                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "actions", e))?;
+            }
+        }
+        (Tag::FrameLabel(tag), "FrameLabelTag") => {
+            if let Some(name) = modification.properties.get("name") {
+                tag.name = serde_json::from_value(name.clone())
+                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "name", e))?;
+            }
+        }
+        (Tag::PlaceObject(tag), "PlaceObjectTag") => {
+            if let Some(matrix) = modification.properties.get("matrix") {
+                tag.matrix = serde_json::from_value(matrix.clone())
+                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "matrix", e))?;
+            }
+            if let Some(color_transform) = modification.properties.get("colorTransform") {
+                tag.color_transform = serde_json::from_value(color_transform.clone())
+                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "colorTransform", e))?;
+            }
+        }
+        (Tag::RemoveObject(tag), "RemoveObjectTag") => {
+            if let Some(depth) = modification.properties.get("depth") {
+                tag.depth = serde_json::from_value(depth.clone())
+                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "depth", e))?;
+            }
+        }
+        _ => return Ok(false),
+    }
+    Ok(true)
+}
+
+/// Property names `apply_tag_modification` understands for each tag type. Anything else in
+/// `properties` is ignored; `None` means the tag type is not supported at all.
+pub(crate) fn supported_properties(tag: &str) -> Option<&'static [&'static str]> {
+    Some(match tag {
+        "DefineBinaryDataTag" | "DefineBitmapTag" | "DoAbcTag" => &["data"],
+        "DefineButtonTag" | "DefineTextTag" => &["records"],
+        "DefineButtonColorTransformTag" => &["transform"],
+        "DefineDynamicTextTag" => EDIT_TEXT_PROPERTIES,
+        "DefineMorphShapeTag" => &["shape"],
+        "DefineShapeTag" => &["shape", "bounds", "records", "styles", "fillStyles", "lineStyles"],
+        "DefineSpriteTag" => &["tags"],
+        "DoActionTag" => &["actions"],
+        "FileAttributesTag" => &["actionScript3", "hasMetadata", "useNetwork", "useGPU"],
+        "FrameLabelTag" => &["name"],
+        "PlaceObjectTag" => &["matrix", "colorTransform"],
+        "RemoveObjectTag" => &["depth"],
+        "SetBackgroundColorTag" => &["backgroundColor"],
+        "SymbolClassTag" => &["symbols"],
+        "DefineSceneAndFrameLabelDataTag" => &["scenes", "labels"],
+        _ => return None,
+    })
+}
+
+/// Applies one modification to every matching tag, in any timeline, and returns how many
+/// tags matched. Timeline tags go through `apply_timeline_modification` instead.
+fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) -> Result<usize> {
+    // Text field properties are read once, with a font selector looked up before the walk.
+    // "@alias" font IDs were already replaced when the modification was resolved.
+    let edit_text = if modification.tag == "DefineDynamicTextTag" {
+        let properties = EditTextProperties::from_properties(&modification.tag, &modification.properties)?;
+        let font_id = properties.resolve_font(movie, &Aliases::default())?;
+        Some((properties, font_id))
+    } else {
+        None
+    };
+
+    let mut touched = 0;
+    walk_tags_mut(&mut movie.tags, &mut |_, tag| {
         match (tag, modification.tag.as_str()) {
             (Tag::DefineBinaryData(tag), "DefineBinaryDataTag") if tag.id == modification.id => {
                 if let Some(data) = modification.properties.get("data") {
                     tag.data = serde_json::from_value(data.clone())
-                        .map_err(|e| format!("Failed to parse binary data: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "data", e))?;
                 }
             }
             (Tag::DefineBitmap(tag), "DefineBitmapTag") if tag.id == modification.id => {
                 if let Some(data) = modification.properties.get("data") {
                     tag.data = serde_json::from_value(data.clone())
-                        .map_err(|e| format!("Failed to parse bitmap data: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "data", e))?;
                 }
             }
             (Tag::DefineButton(tag), "DefineButtonTag") if tag.id == modification.id => {
                 if let Some(records) = modification.properties.get("records") {
                     tag.records = serde_json::from_value(records.clone())
-                        .map_err(|e| format!("Failed to parse button records: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "records", e))?;
                 }
             }
             (Tag::DefineButtonColorTransform(tag), "DefineButtonColorTransformTag")
@@ -1479,47 +1827,46 @@ fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) ->
             {
                 if let Some(transform) = modification.properties.get("transform") {
                     tag.transform = serde_json::from_value(transform.clone())
-                        .map_err(|e| format!("Failed to parse color transform: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "transform", e))?;
                 }
             }
             (Tag::DefineDynamicText(tag), "DefineDynamicTextTag") if tag.id == modification.id => {
-                if let Some(text) = modification.properties.get("text") {
-                    tag.text = serde_json::from_value(text.clone())
-                        .map_err(|e| format!("Failed to parse dynamic text: {}", e))?;
+                if let Some((properties, font_id)) = &edit_text {
+                    properties.apply(tag, *font_id)?;
                 }
             }
             (Tag::DefineMorphShape(tag), "DefineMorphShapeTag") if tag.id == modification.id => {
                 if let Some(shape) = modification.properties.get("shape") {
                     tag.shape = serde_json::from_value(shape.clone())
-                        .map_err(|e| format!("Failed to parse morph shape: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "shape", e))?;
                 }
             }
             (Tag::DefineShape(tag), "DefineShapeTag") if tag.id == modification.id => {
                 if let Some(shape) = modification.properties.get("shape") {
                     tag.shape = serde_json::from_value(shape.clone())
-                        .map_err(|e| format!("Failed to parse shape: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "shape", e))?;
                 } else {
                     if let Some(bounds) = modification.properties.get("bounds") {
                         tag.bounds = serde_json::from_value(bounds.clone())
-                            .map_err(|e| format!("Failed to parse shape bounds: {}", e))?;
+                            .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "bounds", e))?;
                     }
                     if let Some(records) = modification.properties.get("records") {
                         tag.shape.records = serde_json::from_value(records.clone())
-                            .map_err(|e| format!("Failed to parse shape records: {}", e))?;
+                            .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "records", e))?;
                     }
                     if let Some(styles) = modification.properties.get("styles") {
                         tag.shape.initial_styles = serde_json::from_value(styles.clone())
-                            .map_err(|e| format!("Failed to parse shape styles: {}", e))?;
+                            .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "styles", e))?;
                     } else {
                         if let Some(fill_styles) = modification.properties.get("fillStyles") {
                             tag.shape.initial_styles.fill =
                                 serde_json::from_value(fill_styles.clone())
-                                    .map_err(|e| format!("Failed to parse fill styles: {}", e))?;
+                                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "fillStyles", e))?;
                         }
                         if let Some(line_styles) = modification.properties.get("lineStyles") {
                             tag.shape.initial_styles.line =
                                 serde_json::from_value(line_styles.clone())
-                                    .map_err(|e| format!("Failed to parse line styles: {}", e))?;
+                                    .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "lineStyles", e))?;
                         }
                     }
                 }
@@ -1527,29 +1874,23 @@ fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) ->
             (Tag::DefineSprite(tag), "DefineSpriteTag") if tag.id == modification.id => {
                 if let Some(tags) = modification.properties.get("tags") {
                     tag.tags = serde_json::from_value(tags.clone())
-                        .map_err(|e| format!("Failed to parse sprite tags: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "tags", e))?;
                 }
             }
             (Tag::DefineText(tag), "DefineTextTag") if tag.id == modification.id => {
                 if let Some(records) = modification.properties.get("records") {
                     tag.records = serde_json::from_value(records.clone())
-                        .map_err(|e| format!("Failed to parse text records: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "records", e))?;
                 }
             }
 
-            (Tag::DoAbc(tag), "DoAbcTag") if modification.tag == "DoAbcTag" => {
+            (Tag::DoAbc(tag), "DoAbcTag") => {
                 if let Some(data) = modification.properties.get("data") {
                     tag.data = serde_json::from_value(data.clone())
-                        .map_err(|e| format!("Failed to parse ABC data: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "data", e))?;
                 }
             }
-            (Tag::DoAction(tag), "DoActionTag") if modification.tag == "DoActionTag" => {
-                if let Some(actions) = modification.properties.get("actions") {
-                    tag.actions = serde_json::from_value(actions.clone())
-                        .map_err(|e| format!("Failed to parse actions: {}", e))?;
-                }
-            }
-            (Tag::FileAttributes(tag), "FileAttributesTag") if modification.tag == "FileAttributesTag" => {
+            (Tag::FileAttributes(tag), "FileAttributesTag") => {
                 if let Some(props) = modification.properties.as_object() {
                     if let Some(as3) = props.get("actionScript3") {
                         tag.use_as3 = as3.as_bool().unwrap_or(false);
@@ -1565,32 +1906,10 @@ fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) ->
                     }
                 }
             }
-            (Tag::FrameLabel(tag), "FrameLabelTag") => {
-                if let Some(name) = modification.properties.get("name") {
-                    tag.name = serde_json::from_value(name.clone())
-                        .map_err(|e| format!("Failed to parse frame label: {}", e))?;
-                }
-            }
-            (Tag::PlaceObject(tag), "PlaceObjectTag") => {
-                if let Some(matrix) = modification.properties.get("matrix") {
-                    tag.matrix = serde_json::from_value(matrix.clone())
-                        .map_err(|e| format!("Failed to parse matrix: {}", e))?;
-                }
-                if let Some(color_transform) = modification.properties.get("colorTransform") {
-                    tag.color_transform = serde_json::from_value(color_transform.clone())
-                        .map_err(|e| format!("Failed to parse color transform: {}", e))?;
-                }
-            }
-            (Tag::RemoveObject(tag), "RemoveObjectTag") => {
-                if let Some(depth) = modification.properties.get("depth") {
-                    tag.depth = serde_json::from_value(depth.clone())
-                        .map_err(|e| format!("Failed to parse depth: {}", e))?;
-                }
-            }
             (Tag::SetBackgroundColor(tag), "SetBackgroundColorTag") => {
                 if let Some(color) = modification.properties.get("backgroundColor") {
                     let rgba: StraightSRgba8 = serde_json::from_value(color.clone())
-                        .map_err(|e| format!("Failed to parse color: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "backgroundColor", e))?;
                     tag.color = SRgb8 {
                         r: rgba.r,
                         g: rgba.g,
@@ -1601,78 +1920,86 @@ fn apply_tag_modification(movie: &mut Movie, modification: &TagModification) ->
             (Tag::SymbolClass(tag), "SymbolClassTag") => {
                 if let Some(symbols) = modification.properties.get("symbols") {
                     tag.symbols = serde_json::from_value(symbols.clone())
-                        .map_err(|e| format!("Failed to parse symbols: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "symbols", e))?;
                 }
             }
 
             (Tag::DefineSceneAndFrameLabelData(tag), "DefineSceneAndFrameLabelDataTag") => {
                 if let Some(scenes) = modification.properties.get("scenes") {
                     tag.scenes = serde_json::from_value(scenes.clone())
-                        .map_err(|e| format!("Failed to parse scenes: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "scenes", e))?;
                 }
                 if let Some(labels) = modification.properties.get("labels") {
                     tag.labels = serde_json::from_value(labels.clone())
-                        .map_err(|e| format!("Failed to parse labels: {}", e))?;
+                        .map_err(|e| StarDeltaError::invalid_property(&modification.tag, "labels", e))?;
                 }
             }
-            _ => continue,
+            _ => return Ok(()),
         }
-    }
-    Ok(())
+        touched += 1;
+        Ok(())
+    })?;
+    Ok(touched)
 }
 
+#[cfg(feature = "gui")]
 #[command]
 pub fn convert_json_to_swf(
     _handle: AppHandle,
     json_path: String,
     swf_path: String,
-) -> Result<(), String> {
+    compression: Option<SwfCompression>,
+) -> Result<()> {
+    json_to_swf(&json_path, &swf_path, compression.unwrap_or_default())
+}
+
+/// Reads an SWF JSON dump and emits it as a binary SWF at `swf_path`.
+pub fn json_to_swf(json_path: &str, swf_path: &str, compression: SwfCompression) -> Result<()> {
     println!("Starting SWF conversion process...");
     println!("Input JSON: {}", json_path);
     println!("Output SWF: {}", swf_path);
 
     // Read the modified JSON
     println!("Reading modified JSON file...");
-    let json_data = fs::read_to_string(&json_path).map_err(|e| {
+    let json_data = fs::read_to_string(json_path).map_err(|e| {
         println!("Failed to read JSON file: {}", e);
-        format!("Failed to read JSON file '{}': {}", json_path, e)
+        StarDeltaError::io("Failed to read JSON file", json_path, e)
     })?;
 
     // Parse JSON to Movie
     println!("Parsing JSON to Movie structure...");
-    let movie: Movie = serde_json::from_str(&json_data).map_err(|e| {
+    let movie: Movie = from_json_str(&json_data, json_path).inspect_err(|e| {
         println!("Failed to parse JSON to Movie: {}", e);
-        format!("Failed to parse JSON file '{}': {}", json_path, e)
     })?;
 
     // Convert Movie to binary SWF
-    println!("Converting Movie to binary SWF...");
-    let swf_data = emit_swf(&movie, swf_types::CompressionMethod::None).map_err(|e| {
+    println!("Converting Movie to binary SWF ({} compression)...", compression);
+    let swf_data = emit_movie(&movie, compression).inspect_err(|e| {
         println!("Failed to emit SWF: {}", e);
-        format!("Failed to create SWF from JSON '{}': {}", json_path, e)
     })?;
 
     // Write the SWF file directly
     println!("Writing SWF file to: {}", swf_path);
-    fs::write(&swf_path, swf_data).map_err(|e| {
+    fs::write(swf_path, swf_data).map_err(|e| {
         println!("Failed to write SWF file: {}", e);
-        format!("Failed to write SWF file '{}': {}", swf_path, e)
+        StarDeltaError::io("Failed to write SWF file", swf_path, e)
     })?;
 
     println!("SWF conversion completed successfully");
     Ok(())
 }
 
+#[cfg(feature = "gui")]
 #[command]
-pub fn get_file_size(_handle: AppHandle, path: String) -> Result<u64, String> {
-    let metadata = fs::metadata(path.clone()).map_err(|e| {
+pub fn get_file_size(_handle: AppHandle, path: String) -> Result<u64> {
+    let metadata = fs::metadata(&path).map_err(|e| {
         println!("Failed to get metadata for file '{}': {}", path, e);
-        format!("Failed to get file metadata: {}", e)
+        StarDeltaError::io("Failed to get file metadata", &path, e)
     })?;
     Ok(metadata.len())
 }
 
-fn apply_transparency(movie: &mut Movie, shape_ids: &[u16]) -> Result<(), String> {
+fn apply_transparency(movie: &mut Movie, shape_ids: &[u16]) -> Result<()> {
     println!("Making shapes transparent...");
 
     // Ensure we have a high enough SWF version for alpha support
@@ -1680,86 +2007,96 @@ fn apply_transparency(movie: &mut Movie, shape_ids: &[u16]) -> Result<(), String
         movie.header.swf_version = 8;
     }
 
-    // Handle shape transparency
-    for &shape_id in shape_ids {
-        println!("Making shape {} transparent", shape_id);
-
-        // Find and modify the shape tag
-        for i in 0..movie.tags.len() {
-            if let Tag::DefineShape(shape_tag) = &movie.tags[i] {
-                if shape_tag.id == shape_id {
-                    println!("Found shape {} - converting to DefineShape3 and making transparent", shape_id);
-
-                    // Create a new shape with transparent fills
-                    let new_shape = Shape {
-                        initial_styles: ShapeStyles {
-                            fill: vec![
-                                FillStyle::Solid(fill_styles::Solid {
-                                    color: StraightSRgba8 {
-                                        r: 0,
-                                        g: 0,
-                                        b: 0,
-                                        a: 0,  // Alpha 0 will force Shape3
-                                    },
-                                }),
-                                FillStyle::Solid(fill_styles::Solid {
-                                    color: StraightSRgba8 {
-                                        r: 0,
-                                        g: 0,
-                                        b: 0,
-                                        a: 0,  // Alpha 0 will force Shape3
-                                    },
-                                }),
-                            ],
-                            line: Vec::new(),
+    // Handle shape transparency, in any timeline
+    walk_tags_mut(&mut movie.tags, &mut |_, tag| {
+        let Tag::DefineShape(shape_tag) = &*tag else {
+            return Ok(());
+        };
+        let shape_id = shape_tag.id;
+        if !shape_ids.contains(&shape_id) {
+            return Ok(());
+        }
+        println!("Found shape {} - converting to DefineShape3 and making transparent", shape_id);
+
+        // Create a new shape with transparent fills
+        let new_shape = Shape {
+            initial_styles: ShapeStyles {
+                fill: vec![
+                    FillStyle::Solid(fill_styles::Solid {
+                        color: StraightSRgba8 {
+                            r: 0,
+                            g: 0,
+                            b: 0,
+                            a: 0,  // Alpha 0 will force Shape3
                         },
-                        records: shape_tag.shape.records.clone(),
-                    };
+                    }),
+                    FillStyle::Solid(fill_styles::Solid {
+                        color: StraightSRgba8 {
+                            r: 0,
+                            g: 0,
+                            b: 0,
+                            a: 0,  // Alpha 0 will force Shape3
+                        },
+                    }),
+                ],
+                line: Vec::new(),
+            },
+            records: shape_tag.shape.records.clone(),
+        };
 
-                    // Create a new DefineShape tag
-                    let new_tag = Tag::DefineShape(tags::DefineShape {
-                        id: shape_id,
-                        bounds: shape_tag.bounds.clone(),
-                        edge_bounds: None,  // Don't set edge_bounds to avoid forcing Shape4
-                        has_fill_winding: false,
-                        has_non_scaling_strokes: false,
-                        has_scaling_strokes: false,
-                        shape: new_shape,
-                    });
+        // Create a new DefineShape tag
+        let new_tag = Tag::DefineShape(tags::DefineShape {
+            id: shape_id,
+            bounds: shape_tag.bounds.clone(),
+            edge_bounds: None,  // Don't set edge_bounds to avoid forcing Shape4
+            has_fill_winding: false,
+            has_non_scaling_strokes: false,
+            has_scaling_strokes: false,
+            shape: new_shape,
+        });
 
-                    // Replace the old tag with the new one
-                    movie.tags[i] = new_tag;
-                    println!("Successfully converted shape {} to DefineShape3 with transparency", shape_id);
-                    break;
-                }
-            }
-        }
-    }
+        // Replace the old tag with the new one
+        *tag = new_tag;
+        println!("Successfully converted shape {} to DefineShape3 with transparency", shape_id);
+        Ok(())
+    })?;
 
     Ok(())
 }
 
+/// An SWF written by [`process_batch`] and what patching it did.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProcessedFile {
+    pub output_path: String,
+    pub summary: PatchSummary,
+}
+
+#[cfg(feature = "gui")]
 #[command]
 pub fn batch_process_swf(
     _handle: AppHandle,
     config: BatchProcessConfig,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<ProcessedFile>> {
+    process_batch(&config)
+}
+
+/// Runs every mod in a batch configuration and returns the written SWF files.
+pub fn process_batch(config: &BatchProcessConfig) -> Result<Vec<ProcessedFile>> {
     println!("Starting batch SWF processing...");
     let mut processed_files = Vec::new();
 
     // Read and parse the batch configuration
     let config_json = fs::read_to_string(&config.config_file).map_err(|e| {
-        format!("Failed to read batch config file '{}': {}", config.config_file, e)
+        StarDeltaError::io("Failed to read batch config file", &config.config_file, e)
     })?;
 
-    let batch_config: BatchConfiguration = serde_json::from_str(&config_json).map_err(|e| {
-        format!("Failed to parse batch config file '{}': {}", config.config_file, e)
-    })?;
+    let batch_config: BatchConfiguration = from_json_str(&config_json, &config.config_file)?;
 
     // Get the config file's directory for resolving relative paths
     let config_dir = Path::new(&config.config_file)
         .parent()
-        .ok_or_else(|| "Could not determine config file directory".to_string())?;
+        .ok_or_else(|| StarDeltaError::config("Could not determine config file directory"))?;
 
     // Process each mod configuration
     for mod_config in &batch_config.mods {
@@ -1767,7 +2104,7 @@ pub fn batch_process_swf(
         if mod_config.ba2 == Some(true) {
             // Get the BA2 path from user selection or config
             let ba2_path = config.ba2_path.as_ref()
-                .ok_or_else(|| "BA2 path not provided for BA2 mod".to_string())?;
+                .ok_or_else(|| StarDeltaError::config(format!("BA2 path not provided for BA2 mod: {}", mod_config.name)))?;
 
             // Process each file in the BA2
             if let Some(files) = &mod_config.files {
@@ -1782,27 +2119,31 @@ pub fn batch_process_swf(
                     let file_name = Path::new(&file_config.path)
                         .file_name()
                         .and_then(|n| n.to_str())
-                        .ok_or_else(|| format!("Invalid file path in BA2: {}", file_config.path))?;
+                        .ok_or_else(|| StarDeltaError::config(format!("Invalid file path in BA2: {}", file_config.path)))?;
 
                     // Setup paths
-                    let temp_json_path = PathBuf::from(&config.output_directory)
-                        .join(format!("{}.temp.json", file_name));
                     let output_path = PathBuf::from(&config.output_directory)
                         .join(file_name);
+                    let json_dump_path = config.json_dump_directory.as_ref()
+                        .map(|dir| PathBuf::from(dir).join(format!("{}.json", file_name)));
                     let config_path = config_dir.join(&file_config.config);
 
                     println!("Processing BA2 file: {} with config: {}", full_path, config_path.display());
 
                     // Process the file
-                    process_single_file(
-                        _handle.clone(),
+                    let summary = process_single_file(
                         &full_path,
-                        &temp_json_path,
                         &output_path,
                         &config_path,
+                        batch_config.compression,
+                        json_dump_path.as_deref(),
+                        config.strict,
                     )?;
 
-                    processed_files.push(output_path.to_string_lossy().to_string());
+                    processed_files.push(ProcessedFile {
+                        output_path: output_path.to_string_lossy().to_string(),
+                        summary,
+                    });
                 }
             }
         } else {
@@ -1812,32 +2153,36 @@ pub fn batch_process_swf(
                 let swf_path = config.swf_mappings.iter()
                     .find(|m| m.mod_name == mod_config.name)
                     .map(|m| m.swf_path.clone())
-                    .ok_or_else(|| format!("No SWF mapping found for mod: {}", mod_config.name))?;
+                    .ok_or_else(|| StarDeltaError::config(format!("No SWF mapping found for mod: {}", mod_config.name)))?;
 
                 let file_name = Path::new(&swf_path)
                     .file_name()
                     .and_then(|n| n.to_str())
-                    .ok_or_else(|| format!("Invalid SWF file path: {}", swf_path))?;
+                    .ok_or_else(|| StarDeltaError::config(format!("Invalid SWF file path: {}", swf_path)))?;
 
                 // Setup paths
-                let temp_json_path = PathBuf::from(&config.output_directory)
-                    .join(format!("{}.temp.json", file_name));
                 let output_path = PathBuf::from(&config.output_directory)
                     .join(file_name);
+                let json_dump_path = config.json_dump_directory.as_ref()
+                    .map(|dir| PathBuf::from(dir).join(format!("{}.json", file_name)));
                 let config_path = config_dir.join(config_path);
 
                 println!("Processing file: {} with config: {}", swf_path, config_path.display());
 
                 // Process the file
-                process_single_file(
-                    _handle.clone(),
+                let summary = process_single_file(
                     &swf_path,
-                    &temp_json_path,
                     &output_path,
                     &config_path,
+                    batch_config.compression,
+                    json_dump_path.as_deref(),
+                    config.strict,
                 )?;
 
-                processed_files.push(output_path.to_string_lossy().to_string());
+                processed_files.push(ProcessedFile {
+                    output_path: output_path.to_string_lossy().to_string(),
+                    summary,
+                });
             }
         }
     }
@@ -1846,67 +2191,111 @@ pub fn batch_process_swf(
     Ok(processed_files)
 }
 
+#[cfg(feature = "gui")]
+#[command]
+pub fn patch_swf(
+    _handle: AppHandle,
+    swf_path: String,
+    config_path: String,
+    output_path: String,
+    json_dump_path: Option<String>,
+    strict: Option<bool>,
+) -> Result<PatchSummary> {
+    patch_swf_file(
+        &swf_path,
+        Path::new(&config_path),
+        Path::new(&output_path),
+        json_dump_path.as_deref().map(Path::new),
+        strict.unwrap_or(false),
+    )
+}
+
+#[cfg(feature = "gui")]
+#[command]
+pub fn validate_swf_patch(
+    _handle: AppHandle,
+    swf_path: String,
+    config_path: String,
+) -> Result<ValidationReport> {
+    validate_swf_file(&swf_path, Path::new(&config_path))
+}
+
+/// Checks a modification config against an SWF without writing anything.
+pub fn validate_swf_file(input_path: &str, config_path: &Path) -> Result<ValidationReport> {
+    let swf_data = read_swf_file(input_path)?;
+    let patcher = Patcher::from_file(config_path)?;
+    let movie = parse_movie(&swf_data)?;
+    Ok(patcher.validate(&movie))
+}
+
+/// Patches a single SWF with a modification config entirely in memory. The patched movie
+/// is also written as JSON to `json_dump_path` when given. See [`Patcher::strict`].
+/// Returns what the config did to the movie.
+pub fn patch_swf_file(
+    input_path: &str,
+    config_path: &Path,
+    output_path: &Path,
+    json_dump_path: Option<&Path>,
+    strict: bool,
+) -> Result<PatchSummary> {
+    process_single_file(input_path, output_path, config_path, None, json_dump_path, strict)
+}
+
 // Helper function to process a single file (used by both BA2 and non-BA2 paths)
 fn process_single_file(
-    handle: AppHandle,
     input_path: &str,
-    temp_json_path: &Path,
     output_path: &Path,
     config_path: &Path,
-) -> Result<(), String> {
-    // Convert SWF to JSON
-    convert_swf_to_json(
-        handle.clone(),
-        input_path.to_string(),
-        temp_json_path.to_string_lossy().to_string(),
-    )?;
-
-    // Apply modifications
-    apply_json_modifications(
-        handle.clone(),
-        temp_json_path.to_string_lossy().to_string(),
-        config_path.to_string_lossy().to_string(),
-        temp_json_path.to_string_lossy().to_string(),
-    )?;
-
-    // Convert back to SWF
-    convert_json_to_swf(
-        handle.clone(),
-        temp_json_path.to_string_lossy().to_string(),
-        output_path.to_string_lossy().to_string(),
-    )?;
-
-    // Clean up temporary JSON file
-    if let Err(e) = fs::remove_file(temp_json_path) {
-        println!("Warning: Failed to clean up temporary file '{}': {}", temp_json_path.display(), e);
+    default_compression: Option<SwfCompression>,
+    json_dump_path: Option<&Path>,
+    strict: bool,
+) -> Result<PatchSummary> {
+    let swf_data = read_swf_file(input_path)?;
+    let patcher = Patcher::from_file(config_path)?
+        .strict(strict)
+        .default_compression(default_compression);
+
+    // Parse, modify and emit without touching the output directory until the end
+    let patched = patcher.patch_bytes(&swf_data)?;
+
+    if let Some(json_dump_path) = json_dump_path {
+        println!("Writing intermediate JSON to: {}", json_dump_path.display());
+        let json = serde_json::to_string_pretty(&patched.movie)
+            .map_err(|e| StarDeltaError::JsonSerialize { message: e.to_string() })?;
+        fs::write(json_dump_path, json)
+            .map_err(|e| StarDeltaError::io("Failed to write JSON file", json_dump_path, e))?;
     }
 
-    Ok(())
+    fs::write(output_path, &patched.data)
+        .map_err(|e| StarDeltaError::io("Failed to write SWF file", output_path, e))?;
+
+    Ok(patched.summary)
 }
 
+#[cfg(feature = "gui")]
 #[command]
-pub fn read_file_to_string(_handle: AppHandle, path: String) -> Result<String, String> {
+pub fn read_file_to_string(_handle: AppHandle, path: String) -> Result<String> {
     fs::read_to_string(&path).map_err(|e| {
         println!("Failed to read file '{}': {}", path, e);
-        format!("Failed to read file: {}", e)
+        StarDeltaError::io("Failed to read file", &path, e)
     })
 }
 
-fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch], config_path: &str, handle: AppHandle) -> Result<(), String> {
+fn apply_actionscript_patches(
+    movie: &mut Movie,
+    patches: &[ActionScriptPatch],
+    aliases: &Aliases,
+    config_dir: &Path,
+) -> Result<()> {
     // Create a temporary directory for compilation
     let temp_dir = TempDir::new()
-        .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
-
-    // Get the config file's directory
-    let config_dir = Path::new(config_path)
-        .parent()
-        .ok_or_else(|| "Could not determine config file directory".to_string())?;
+        .map_err(|e| StarDeltaError::io("Failed to create temporary directory", std::env::temp_dir(), e))?;
 
     for patch in patches {
         // Read the ActionScript source file
         let source_path = config_dir.join(&patch.source_file);
         let mut source_code = fs::read_to_string(&source_path)
-            .map_err(|e| format!("Failed to read ActionScript file '{}': {}", patch.source_file, e))?;
+            .map_err(|e| StarDeltaError::io("Failed to read ActionScript file", &source_path, e))?;
 
         // If package_name is provided, ensure the code has the correct package declaration
         if let Some(package_name) = &patch.package_name {
@@ -1943,18 +2332,17 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
         let temp_swf_path = temp_dir.path().join("temp.swf");
 
         // Write the current movie to the temp SWF
-        let swf_data = emit_swf(&movie, swf_types::CompressionMethod::None)
-            .map_err(|e| format!("Failed to write temporary SWF: {}", e))?;
+        let swf_data = emit_movie(movie, SwfCompression::None)?;
         fs::write(&temp_swf_path, swf_data)
-            .map_err(|e| format!("Failed to write temporary SWF: {}", e))?;
+            .map_err(|e| StarDeltaError::io("Failed to write temporary SWF", &temp_swf_path, e))?;
 
         // Write the modified ActionScript file to the temp directory
         let temp_as_path = temp_dir.path().join("Main.as");
         fs::write(&temp_as_path, source_code)
-            .map_err(|e| format!("Failed to write temporary AS file: {}", e))?;
+            .map_err(|e| StarDeltaError::io("Failed to write temporary AS file", &temp_as_path, e))?;
 
         // Compile the ActionScript using JPEXS
-        let abc_data = compile_with_jpexs(handle.clone(), &temp_as_path, &temp_swf_path)?;
+        let abc_data = compile_with_jpexs(&temp_as_path, &temp_swf_path)?;
 
         // Create a new DoABC tag with the compiled code
         let new_tag = Tag::DoAbc(swf_types::tags::DoAbc {
@@ -1965,7 +2353,7 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
         // Add or replace the tag based on insert mode
         match patch.insert_mode {
             ActionScriptInsertMode::Add => {
-                movie.tags.push(new_tag);
+                insert_do_abc(&mut movie.tags, new_tag, patch.before.as_ref())?;
             },
             ActionScriptInsertMode::Replace => {
                 if let Some(class_name) = &patch.class_name {
@@ -1981,7 +2369,7 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
                         }
                     }
                     if !found {
-                        movie.tags.push(new_tag);
+                        insert_do_abc(&mut movie.tags, new_tag, patch.before.as_ref())?;
                     }
                 } else {
                     // If no class name specified, replace first DoAbc tag
@@ -1994,7 +2382,7 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
                         }
                     }
                     if !found {
-                        movie.tags.push(new_tag);
+                        insert_do_abc(&mut movie.tags, new_tag, patch.before.as_ref())?;
                     }
                 }
             },
@@ -2002,6 +2390,11 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
 
         // Handle symbol class bindings if present
         if let Some(bindings) = &patch.symbol_bindings {
+            let bindings = bindings
+                .iter()
+                .map(|b| Ok((b.symbol_id.resolve(movie, aliases)?, b.class_name.clone())))
+                .collect::<Result<Vec<(u16, String)>>>()?;
+
             // Find or create a SymbolClass tag
             let mut symbol_class_tag = None;
             for tag in &mut movie.tags {
@@ -2016,18 +2409,15 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
             if let Some(symbol_tag) = symbol_class_tag {
                 // Keep existing bindings that aren't being replaced
                 for binding in &symbol_tag.symbols {
-                    if !bindings.iter().any(|b| b.symbol_id == binding.id) {
+                    if !bindings.iter().any(|(id, _)| *id == binding.id) {
                         new_symbols.push(binding.clone());
                     }
                 }
             }
 
             // Add new bindings
-            for binding in bindings {
-                new_symbols.push(swf_types::NamedId {
-                    id: binding.symbol_id,
-                    name: binding.class_name.clone(),
-                });
+            for (id, name) in bindings {
+                new_symbols.push(swf_types::NamedId { id, name });
             }
 
             // Create or update the SymbolClass tag
@@ -2045,7 +2435,7 @@ fn apply_actionscript_patches(movie: &mut Movie, patches: &[ActionScriptPatch],
                 }
             }
             if !found {
-                movie.tags.push(symbol_class_tag);
+                insert_definition(&mut movie.tags, symbol_class_tag, None)?;
             }
         }
     }
@@ -2088,31 +2478,32 @@ fn contains_class_name(abc_data: &[u8], class_name: &str) -> bool {
     abc_data.windows(class_bytes.len()).any(|window| window == class_bytes)
 }
 
-fn check_java_installation() -> Result<(), String> {
+fn check_java_installation() -> Result<()> {
     let output = Command::new("java")
         .arg("-version")
         .output()
-        .map_err(|_| "Java is not installed or not accessible".to_string())?;
+        .map_err(|_| StarDeltaError::JavaNotFound)?;
 
     if !output.status.success() {
-        return Err("Failed to verify Java installation".to_string());
+        return Err(StarDeltaError::JavaNotFound);
     }
     Ok(())
 }
 
-fn compile_with_jpexs(handle: AppHandle, as_path: &Path, swf_path: &Path) -> Result<Vec<u8>, String> {
+fn compile_with_jpexs(as_path: &Path, swf_path: &Path) -> Result<Vec<u8>> {
     // Check Java installation first
     check_java_installation()?;
 
     // Create a temporary directory for JPEXS output
     let output_dir = TempDir::new()
-        .map_err(|e| format!("Failed to create temporary output directory: {}", e))?;
+        .map_err(|e| StarDeltaError::io("Failed to create temporary output directory", std::env::temp_dir(), e))?;
 
     // Get the path to ffdec.jar from the bundled resources
-    let resource_path = std::env::current_exe()
-        .map_err(|e| format!("Failed to get executable path: {}", e))?
+    let current_exe = std::env::current_exe()
+        .map_err(|e| StarDeltaError::io("Failed to get executable path", "<current executable>", e))?;
+    let resource_path = current_exe
         .parent()
-        .ok_or_else(|| "Failed to get parent directory".to_string())?
+        .ok_or_else(|| StarDeltaError::config("Failed to get parent directory of the executable"))?
         .join("resources")
         .join("ffdec.jar");
 
@@ -2122,7 +2513,7 @@ fn compile_with_jpexs(handle: AppHandle, as_path: &Path, swf_path: &Path) -> Res
     let output_swf = output_dir.path().join("output.swf");
 
     // Run JPEXS to import the ActionScript
-    let status = Command::new("java")
+    let output = Command::new("java")
         .args([
             "-jar",
             resource_path.to_str().unwrap(),
@@ -2131,27 +2522,23 @@ fn compile_with_jpexs(handle: AppHandle, as_path: &Path, swf_path: &Path) -> Res
             swf_path.to_str().unwrap(),
             output_swf.to_str().unwrap(),
         ])
-        .status()
-        .map_err(|e| format!("Failed to execute JPEXS: {}", e))?;
+        .output()
+        .map_err(|e| StarDeltaError::io("Failed to execute JPEXS", &resource_path, e))?;
 
-    if !status.success() {
-        return Err("JPEXS script import failed".to_string());
+    if !output.status.success() {
+        return Err(StarDeltaError::ActionScriptCompile {
+            output: format!(
+                "{}{}",
+                String::from_utf8_lossy(&output.stdout),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+        });
     }
 
     // Now we need to extract the ABC tag from the output SWF
-    // First convert the SWF to JSON so we can find the ABC tag
-    let temp_json = output_dir.path().join("temp.json");
-    convert_swf_to_json(
-        handle,
-        output_swf.to_string_lossy().to_string(),
-        temp_json.to_string_lossy().to_string(),
-    )?;
-
-    // Read and parse the JSON
-    let json_data = fs::read_to_string(&temp_json)
-        .map_err(|e| format!("Failed to read temporary JSON: {}", e))?;
-    let movie: Movie = serde_json::from_str(&json_data)
-        .map_err(|e| format!("Failed to parse temporary JSON: {}", e))?;
+    let output_data = fs::read(&output_swf)
+        .map_err(|e| StarDeltaError::io("Failed to read compiled SWF", &output_swf, e))?;
+    let movie = parse_movie(&output_data)?;
 
     // Find the first DoAbc tag and return its data
     for tag in movie.tags {
@@ -2160,5 +2547,403 @@ fn compile_with_jpexs(handle: AppHandle, as_path: &Path, swf_path: &Path) -> Res
         }
     }
 
-    Err("No ABC tag found in compiled SWF".to_string())
+    Err(StarDeltaError::ActionScriptCompile {
+        output: "No ABC tag found in compiled SWF".to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::{movie, tag, tag_list};
+
+    #[test]
+    fn patch_swf_file_keeps_compression_and_dumps_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("menu.swf");
+        let swf = emit_movie(&movie(tag_list(json!([{ "type": "ShowFrame" }]))), SwfCompression::Zlib).unwrap();
+        fs::write(&input, swf).unwrap();
+        let config = dir.path().join("config.json");
+        fs::write(&config, json!({ "swf": { "modifications": [] } }).to_string()).unwrap();
+
+        let output = dir.path().join("patched.swf");
+        let dump = dir.path().join("patched.json");
+        patch_swf_file(input.to_str().unwrap(), &config, &output, Some(&dump), false).unwrap();
+
+        assert_eq!(SwfCompression::detect(&fs::read(&output).unwrap()), SwfCompression::Zlib);
+        let dumped: Movie = serde_json::from_str(&fs::read_to_string(&dump).unwrap()).unwrap();
+        assert_eq!(dumped.tags.len(), 1);
+    }
+
+    fn place_object() -> Tag {
+        tag(json!({ "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 2 }))
+    }
+
+    #[test]
+    fn merge_patch_follows_rfc_7386() {
+        let mut target = json!({ "a": "b", "c": { "d": "e", "f": "g" }, "list": [1, 2] });
+        merge_patch(&mut target, &json!({ "a": "z", "c": { "f": null }, "list": [3], "new": { "x": 1 } }));
+        assert_eq!(target, json!({ "a": "z", "c": { "d": "e" }, "list": [3], "new": { "x": 1 } }));
+
+        let mut target = json!({ "a": 1 });
+        merge_patch(&mut target, &json!({ "a": { "b": null, "c": 2 } }));
+        assert_eq!(target, json!({ "a": { "c": 2 } }));
+
+        let mut target = json!({ "a": 1 });
+        merge_patch(&mut target, &json!("replaced"));
+        assert_eq!(target, json!("replaced"));
+    }
+
+    #[test]
+    fn merge_into_tag_keeps_unlisted_properties() {
+        let merged = merge_into_tag(&place_object(), "PlaceObjectTag", &json!({ "depth": 3, "name": "hero" })).unwrap();
+        let Tag::PlaceObject(place) = merged else {
+            panic!("expected a PlaceObject");
+        };
+        assert_eq!(place.depth, 3);
+        assert_eq!(place.name.as_deref(), Some("hero"));
+        assert_eq!(place.character_id, Some(2));
+        assert!(!place.is_update);
+    }
+
+    #[test]
+    fn merge_into_tag_names_the_wrongly_typed_property() {
+        let property = |properties: serde_json::Value| {
+            match merge_into_tag(&place_object(), "PlaceObjectTag", &properties).unwrap_err() {
+                StarDeltaError::InvalidProperty { tag, property, .. } => {
+                    assert_eq!(tag, "PlaceObjectTag");
+                    property
+                }
+                e => panic!("unexpected error: {}", e),
+            }
+        };
+        assert_eq!(property(json!({ "depth": 4, "ratio": "fast" })), "ratio");
+        assert_eq!(property(json!({ "depth": -1 })), "depth");
+        assert_eq!(property(json!({ "name": "hero", "matrix": { "translate_x": "left" } })), "matrix.translate_x");
+    }
+
+    fn patch_operations(operations: serde_json::Value) -> json_patch::Patch {
+        serde_json::from_value(operations).unwrap()
+    }
+
+    #[test]
+    fn json_patch_applies_when_tests_pass() {
+        let mut movie = movie(vec![place_object()]);
+        let patch = patch_operations(json!([
+            { "op": "test", "path": "/tags/0/character_id", "value": 2 },
+            { "op": "replace", "path": "/tags/0/depth", "value": 5 }
+        ]));
+        apply_json_patch(&mut movie, &patch).unwrap();
+        assert!(matches!(&movie.tags[0], Tag::PlaceObject(p) if p.depth == 5));
+    }
+
+    #[test]
+    fn failed_json_patch_test_leaves_movie_unchanged() {
+        let mut movie = movie(vec![place_object()]);
+        let patch = patch_operations(json!([
+            { "op": "replace", "path": "/tags/0/depth", "value": 5 },
+            { "op": "test", "path": "/tags/0/character_id", "value": 9 }
+        ]));
+        let err = apply_json_patch(&mut movie, &patch).unwrap_err();
+        assert!(matches!(err, StarDeltaError::JsonPatch { operation: Some(1), ref path, .. } if path == "/tags/0/character_id"));
+        assert!(matches!(&movie.tags[0], Tag::PlaceObject(p) if p.depth == 1));
+    }
+
+    fn apply_config(movie: &mut Movie, config: serde_json::Value) -> PatchSummary {
+        apply_config_in(Path::new("."), movie, config)
+    }
+
+    fn apply_config_in(base_dir: &Path, movie: &mut Movie, config: serde_json::Value) -> PatchSummary {
+        Patcher::from_json(&config.to_string(), base_dir).unwrap().apply(movie).unwrap()
+    }
+
+    /// Sprite 1 shows shape 3 at depth 1 over two frames labelled `idle`, and is placed on
+    /// the root timeline under another `idle` label.
+    fn labelled_movie() -> Movie {
+        movie(tag_list(json!([
+            {
+                "type": "DefineSprite",
+                "id": 1,
+                "frame_count": 2,
+                "tags": [
+                    { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 3 },
+                    { "type": "FrameLabel", "name": "idle", "is_anchor": false },
+                    { "type": "ShowFrame" },
+                    { "type": "PlaceObject", "is_update": true, "depth": 1, "ratio": 1 },
+                    { "type": "FrameLabel", "name": "idle", "is_anchor": false },
+                    { "type": "ShowFrame" }
+                ]
+            },
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 1 },
+            { "type": "FrameLabel", "name": "idle", "is_anchor": false },
+            { "type": "ShowFrame" }
+        ])))
+    }
+
+    fn labels(tags: &[Tag]) -> Vec<&str> {
+        tags.iter()
+            .filter_map(|tag| match tag {
+                Tag::FrameLabel(label) => Some(label.name.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn sprite_tags(movie: &Movie) -> &[Tag] {
+        match &movie.tags[0] {
+            Tag::DefineSprite(sprite) => &sprite.tags,
+            _ => panic!("expected a DefineSprite"),
+        }
+    }
+
+    #[test]
+    fn scope_selects_frame_of_a_sprite() {
+        let mut movie = labelled_movie();
+        let summary = apply_config(&mut movie, json!({ "swf": { "modifications": [{
+            "tag": "FrameLabelTag",
+            "scope": { "sprite": 1, "frame": 1 },
+            "properties": { "name": "hover" }
+        }] } }));
+        assert_eq!(summary.modifications[0].touched, 1);
+        assert_eq!(labels(sprite_tags(&movie)), ["idle", "hover"]);
+        assert_eq!(labels(&movie.tags), ["idle"]);
+    }
+
+    #[test]
+    fn scope_follows_the_character_at_a_depth_through_updates() {
+        let mut movie = labelled_movie();
+        let summary = apply_config(&mut movie, json!({ "swf": { "modifications": [{
+            "tag": "PlaceObjectTag",
+            "mode": "merge",
+            "scope": { "sprite": 1, "character": 3 },
+            "properties": { "ratio": 7 }
+        }] } }));
+        assert_eq!(summary.modifications[0].touched, 2);
+        let ratios: Vec<_> = sprite_tags(&movie)
+            .iter()
+            .filter_map(|tag| match tag {
+                Tag::PlaceObject(place) => Some(place.ratio),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(ratios, [Some(7), Some(7)]);
+        assert!(matches!(&movie.tags[1], Tag::PlaceObject(place) if place.ratio.is_none()));
+    }
+
+    #[test]
+    fn imports_reuse_assets_the_movie_already_imports() {
+        let imported = || {
+            movie(tag_list(json!([
+                { "type": "ImportAssets", "url": "fonts_en.swf", "assets": [{ "id": 5, "name": "$Main" }] },
+                { "type": "ShowFrame" }
+            ])))
+        };
+        let config = |main: serde_json::Value| {
+            json!({ "swf": { "modifications": [] }, "new_elements": { "imports": [{
+                "url": "fonts_en.swf",
+                "assets": [main, { "name": "$Title" }]
+            }] } })
+        };
+
+        let mut movie = imported();
+        let summary = apply_config(&mut movie, config(json!({ "name": "$Main" })));
+        let Tag::ImportAssets(import) = &movie.tags[0] else {
+            panic!("expected an ImportAssets");
+        };
+        let assets: Vec<_> = import.assets.iter().map(|a| (a.id, a.name.as_str())).collect();
+        assert_eq!(assets, [(5, "$Main"), (6, "$Title")]);
+        assert_eq!(movie.tags.len(), 2);
+        assert_eq!(summary.assigned.len(), 1);
+
+        let conflicting = config(json!({ "name": "$Main", "id": 7 }));
+        let patcher = Patcher::from_json(&conflicting.to_string(), ".").unwrap();
+        assert!(patcher.apply(&mut imported()).is_err());
+    }
+
+    #[test]
+    fn shape_targets_take_an_svg_id_or_the_whole_drawing() {
+        let element: ShapeTarget = serde_json::from_value(json!({ "svg_id": "hover", "shape": { "class": "Star" } })).unwrap();
+        assert_eq!(element.svg_id(), Some("hover"));
+        assert!(matches!(element.shape(), CharacterRef::Target(Target::Class(name)) if name == "Star"));
+
+        let whole: ShapeTarget = serde_json::from_value(json!(3003)).unwrap();
+        assert_eq!(whole.svg_id(), None);
+        assert!(matches!(whole.shape(), CharacterRef::Id(3003)));
+    }
+
+    #[test]
+    fn swf_removal_by_class_runs_once() {
+        let mut movie = movie(tag_list(json!([
+            { "type": "DefineSprite", "id": 1, "frame_count": 0, "tags": [] },
+            { "type": "SymbolClass", "symbols": [{ "id": 1, "name": "Star" }] },
+            { "type": "ShowFrame" }
+        ])));
+        let summary = apply_config(&mut movie, json!({ "swf": {
+            "modifications": [],
+            "remove_elements": { "sprites": [{ "class": "Star" }] }
+        } }));
+        assert_eq!(summary.removed.iter().map(|r| r.id).collect::<Vec<_>>(), [1]);
+        assert!(!movie.tags.iter().any(|tag| matches!(tag, Tag::DefineSprite(_))));
+        assert!(matches!(&movie.tags[0], Tag::SymbolClass(symbols) if symbols.symbols.is_empty()));
+    }
+
+    #[test]
+    fn swf_removal_by_alias_and_export() {
+        let mut movie = movie(tag_list(json!([
+            { "type": "DefineSprite", "id": 1, "frame_count": 0, "tags": [] },
+            { "type": "ExportAssets", "assets": [{ "id": 1, "name": "Star" }] },
+            { "type": "ShowFrame" }
+        ])));
+        let summary = apply_config(&mut movie, json!({ "swf": {
+            "modifications": [],
+            "new_elements": { "sprites": [{ "alias": "spare", "frame_count": 0, "tags": [] }] },
+            "remove_elements": { "sprites": ["@spare", { "export": "Star" }] }
+        } }));
+        let mut removed: Vec<_> = summary.removed.iter().map(|r| r.id).collect();
+        removed.sort();
+        assert_eq!(removed, [1, 2]);
+        assert!(!movie.tags.iter().any(|tag| matches!(tag, Tag::DefineSprite(_))));
+    }
+
+    /// Writes red rectangles `small.svg` (10x10 px) and `wide.svg` (40x20 px), and an
+    /// unfilled `outline.svg` of the small one.
+    fn svg_dir() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, width, height, style) in [
+            ("small.svg", 10, 10, r#"fill="red""#),
+            ("wide.svg", 40, 20, r#"fill="red""#),
+            ("outline.svg", 10, 10, r#"fill="none" stroke="blue""#),
+        ] {
+            let svg = format!(
+                r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}"><rect width="{w}" height="{h}" {s}/></svg>"#,
+                w = width,
+                h = height,
+                s = style
+            );
+            fs::write(dir.path().join(name), svg).unwrap();
+        }
+        dir
+    }
+
+    fn shape_tag(movie: &Movie) -> &tags::DefineShape {
+        movie
+            .tags
+            .iter()
+            .find_map(|tag| match tag {
+                Tag::DefineShape(shape) => Some(shape),
+                _ => None,
+            })
+            .expect("expected a DefineShape")
+    }
+
+    fn is_transparent(shape: &tags::DefineShape) -> bool {
+        let fills = &shape.shape.initial_styles.fill;
+        !fills.is_empty() && fills.iter().all(|fill| matches!(fill, FillStyle::Solid(solid) if solid.color.a == 0))
+    }
+
+    fn width(shape: &tags::DefineShape) -> i32 {
+        shape.bounds.x_max - shape.bounds.x_min
+    }
+
+    #[test]
+    fn transparency_and_replacement_reach_new_shapes_by_alias() {
+        let dir = svg_dir();
+        let new_shape = json!({ "shapes": [{ "source": "small.svg", "alias": "star" }] });
+
+        let mut transparent = movie(tag_list(json!([{ "type": "ShowFrame" }])));
+        apply_config_in(dir.path(), &mut transparent, json!({
+            "swf": { "modifications": [] },
+            "new_elements": new_shape,
+            "transparent": ["@star"]
+        }));
+        assert!(is_transparent(shape_tag(&transparent)));
+
+        let mut replaced = movie(tag_list(json!([{ "type": "ShowFrame" }])));
+        apply_config_in(dir.path(), &mut replaced, json!({
+            "swf": { "modifications": [] },
+            "new_elements": new_shape,
+            "file": [{ "source": "wide.svg", "shapes": ["@star"] }]
+        }));
+        assert_eq!(width(shape_tag(&replaced)), 40 * 20);
+    }
+
+    #[test]
+    fn transparency_and_replacement_take_selectors() {
+        let dir = svg_dir();
+        let placed = || {
+            let mut movie = movie(tag_list(json!([{ "type": "ShowFrame" }])));
+            apply_config_in(dir.path(), &mut movie, json!({
+                "swf": { "modifications": [] },
+                "new_elements": { "shapes": [{ "source": "small.svg", "place": { "depth": 1, "name": "bg" } }] }
+            }));
+            assert_eq!(width(shape_tag(&movie)), 10 * 20);
+            movie
+        };
+
+        let mut transparent = placed();
+        apply_config_in(dir.path(), &mut transparent, json!({
+            "swf": { "modifications": [] },
+            "transparent": [{ "instance": "bg" }]
+        }));
+        assert!(is_transparent(shape_tag(&transparent)));
+
+        let mut replaced = placed();
+        apply_config_in(dir.path(), &mut replaced, json!({
+            "swf": { "modifications": [] },
+            "file": [{ "source": "wide.svg", "shapes": [{ "instance": "bg" }] }]
+        }));
+        assert_eq!(width(shape_tag(&replaced)), 40 * 20);
+    }
+
+    /// Fill styles the records of `shape` switch to.
+    fn record_fills(shape: &tags::DefineShape) -> Vec<&FillStyle> {
+        shape
+            .shape
+            .records
+            .iter()
+            .filter_map(|record| match record {
+                ShapeRecord::StyleChange(change) => change.new_styles.as_ref(),
+                _ => None,
+            })
+            .flat_map(|styles| &styles.fill)
+            .collect()
+    }
+
+    #[test]
+    fn replacement_keeps_the_original_fills_only_for_unfilled_drawings() {
+        let dir = svg_dir();
+        // Transparency gives the shape initial fills, like those of a bitmap-filled shape
+        let filled = || {
+            let mut movie = movie(tag_list(json!([{ "type": "ShowFrame" }])));
+            apply_config_in(dir.path(), &mut movie, json!({
+                "swf": { "modifications": [] },
+                "new_elements": { "shapes": [{ "source": "small.svg", "alias": "star" }] },
+                "transparent": ["@star"]
+            }));
+            movie
+        };
+        let replace = |movie: &mut Movie, source: &str| {
+            let id = shape_tag(movie).id;
+            apply_config_in(dir.path(), movie, json!({
+                "swf": { "modifications": [] },
+                "file": [{ "source": source, "shapes": [id] }]
+            }));
+        };
+
+        let mut outline = filled();
+        replace(&mut outline, "outline.svg");
+        let shape = shape_tag(&outline);
+        assert!(is_transparent(shape));
+        let fills = record_fills(shape);
+        assert_eq!(fills.len(), 2);
+        assert!(fills.iter().all(|fill| matches!(fill, FillStyle::Solid(solid) if solid.color.a == 0)));
+
+        let mut red = filled();
+        replace(&mut red, "small.svg");
+        let shape = shape_tag(&red);
+        assert!(shape.shape.initial_styles.fill.is_empty());
+        assert!(matches!(record_fills(shape)[..], [FillStyle::Solid(solid)] if solid.color.r == 255 && solid.color.a == 255));
+    }
 }
@@ -0,0 +1,225 @@
+use serde::{de, Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt;
+use swf_types::{Movie, Tag};
+
+use crate::error::{Result, StarDeltaError};
+use crate::ids::Aliases;
+use crate::walk::find_sprite;
+
+/// Selects a character by a name that survives ID renumbering between game updates.
+///
+/// Written in configs as `{"class": "InventoryMenu_ItemCard"}`, `{"export": "..."}`,
+/// `{"instance": "root.menu.title_tf"}` or `{"font": "$MAIN_Font"}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Target {
+    /// ActionScript class bound to the character by a `SymbolClass` tag
+    Class(String),
+    /// Name the character is exported under by an `ExportAssets` tag
+    Export(String),
+    /// Dot-separated instance names from the root timeline through nested sprites
+    Instance(String),
+    /// Name of an embedded `DefineFont3` or `DefineFont4` font, or else of a font imported
+    /// from a library by `ImportAssets`
+    Font(String),
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Target::Class(name) => write!(f, "class '{}'", name),
+            Target::Export(name) => write!(f, "export '{}'", name),
+            Target::Instance(path) => write!(f, "instance '{}'", path),
+            Target::Font(name) => write!(f, "font '{}'", name),
+        }
+    }
+}
+
+impl Target {
+    /// Returns the ID of the character this target names in `movie`.
+    pub fn resolve(&self, movie: &Movie) -> Result<u16> {
+        let id = match self {
+            Target::Class(name) => movie.tags.iter().find_map(|tag| match tag {
+                Tag::SymbolClass(t) => t.symbols.iter().find(|s| &s.name == name).map(|s| s.id),
+                _ => None,
+            }),
+            Target::Export(name) => movie.tags.iter().find_map(|tag| match tag {
+                Tag::ExportAssets(t) => t.assets.iter().find(|a| &a.name == name).map(|a| a.id),
+                _ => None,
+            }),
+            Target::Instance(path) => resolve_instance(movie, path),
+            Target::Font(name) => movie
+                .tags
+                .iter()
+                .find_map(|tag| match tag {
+                    Tag::DefineFont(font) if &font.font_name == name => Some(font.id),
+                    Tag::DefineCffFont(font) if &font.font_name == name => Some(font.id),
+                    _ => None,
+                })
+                .or_else(|| {
+                    movie.tags.iter().find_map(|tag| match tag {
+                        Tag::ImportAssets(t) => t.assets.iter().find(|a| &a.name == name).map(|a| a.id),
+                        _ => None,
+                    })
+                }),
+        };
+        id.ok_or_else(|| StarDeltaError::UnresolvedTarget { target: self.to_string() })
+    }
+}
+
+/// The `alias` of a new element in the same config, written `"@name"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Alias(pub String);
+
+impl<'de> Deserialize<'de> for Alias {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        match value.strip_prefix('@') {
+            Some(name) if !name.is_empty() => Ok(Alias(name.to_string())),
+            _ => Err(de::Error::custom(format!("expected \"@alias\", found \"{}\"", value))),
+        }
+    }
+}
+
+impl fmt::Display for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "alias '@{}'", self.0)
+    }
+}
+
+/// A character given by numeric ID, by a [`Target`] or by the [`Alias`] of a new element,
+/// e.g. in `transparent`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum CharacterRef {
+    Id(u16),
+    Alias(Alias),
+    Target(Target),
+}
+
+impl CharacterRef {
+    pub fn resolve(&self, movie: &Movie, aliases: &Aliases) -> Result<u16> {
+        match self {
+            CharacterRef::Id(id) => Ok(*id),
+            CharacterRef::Alias(alias) => aliases.get(&alias.0),
+            CharacterRef::Target(target) => target.resolve(movie),
+        }
+    }
+}
+
+impl fmt::Display for CharacterRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CharacterRef::Id(id) => write!(f, "ID {}", id),
+            CharacterRef::Alias(alias) => alias.fmt(f),
+            CharacterRef::Target(target) => target.fmt(f),
+        }
+    }
+}
+
+/// Resolves every reference in `refs`, failing on the first that matches nothing.
+pub fn resolve_all(movie: &Movie, aliases: &Aliases, refs: &[CharacterRef]) -> Result<Vec<u16>> {
+    refs.iter().map(|r| r.resolve(movie, aliases)).collect()
+}
+
+/// Finds the character placed under `name` in a timeline. Later `PlaceObject` tags may name
+/// an existing depth without repeating its character ID, so placements are tracked by depth.
+fn find_instance(timeline: &[Tag], name: &str) -> Option<u16> {
+    let mut depths = HashMap::new();
+    for tag in timeline {
+        if let Tag::PlaceObject(place) = tag {
+            if let Some(id) = place.character_id {
+                depths.insert(place.depth, id);
+            }
+            if place.name.as_deref() == Some(name) {
+                return depths.get(&place.depth).copied();
+            }
+        }
+    }
+    None
+}
+
+fn resolve_instance(movie: &Movie, path: &str) -> Option<u16> {
+    let mut names = path.split('.').peekable();
+    if names.peek() == Some(&"root") {
+        names.next();
+    }
+
+    let mut timeline = movie.tags.as_slice();
+    let mut id = None;
+    for name in names {
+        // Every name before the last must be a sprite instance to descend into
+        if let Some(parent) = id {
+            timeline = find_sprite(&movie.tags, parent)?.tags.as_slice();
+        }
+        id = Some(find_instance(timeline, name)?);
+    }
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::ids::IdAllocator;
+    use crate::test_util::{movie, tag_list};
+
+    /// Sprite 1 holds shape 3 as `title_tf` and is placed on the root timeline as `menu`.
+    fn menu_movie() -> Movie {
+        movie(tag_list(json!([
+            {
+                "type": "DefineSprite",
+                "id": 1,
+                "frame_count": 1,
+                "tags": [
+                    { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 3 },
+                    { "type": "PlaceObject", "is_update": true, "depth": 1, "name": "title_tf" },
+                    { "type": "ShowFrame" }
+                ]
+            },
+            { "type": "PlaceObject", "is_update": false, "depth": 1, "character_id": 1, "name": "menu" },
+            { "type": "ExportAssets", "assets": [{ "id": 1, "name": "Menu" }] },
+            { "type": "SymbolClass", "symbols": [{ "id": 1, "name": "InventoryMenu" }] },
+            { "type": "ShowFrame" }
+        ])))
+    }
+
+    #[test]
+    fn targets_resolve_by_class_export_and_instance() {
+        let movie = menu_movie();
+        let resolve = |target: Target| target.resolve(&movie);
+        assert_eq!(resolve(Target::Class("InventoryMenu".to_string())).unwrap(), 1);
+        assert_eq!(resolve(Target::Export("Menu".to_string())).unwrap(), 1);
+        assert_eq!(resolve(Target::Instance("root.menu.title_tf".to_string())).unwrap(), 3);
+        assert_eq!(resolve(Target::Instance("menu".to_string())).unwrap(), 1);
+        assert!(matches!(
+            resolve(Target::Instance("menu.missing".to_string())),
+            Err(StarDeltaError::UnresolvedTarget { .. })
+        ));
+    }
+
+    #[test]
+    fn character_refs_read_ids_aliases_and_targets() {
+        let movie = menu_movie();
+        let mut ids = IdAllocator::new(&movie);
+        let star = ids.assign(None, "new_elements.shapes[0]".to_string(), "DefineShapeTag", Some("star")).unwrap();
+
+        let refs: Vec<CharacterRef> = serde_json::from_value(json!([4, "@star", { "export": "Menu" }])).unwrap();
+        assert_eq!(resolve_all(&movie, ids.aliases(), &refs).unwrap(), [4, star, 1]);
+
+        assert!(serde_json::from_value::<CharacterRef>(json!("star")).is_err());
+        assert!(CharacterRef::Alias(Alias("moon".to_string())).resolve(&movie, ids.aliases()).is_err());
+    }
+
+    #[test]
+    fn fonts_fall_back_to_imported_names() {
+        let movie = movie(tag_list(json!([
+            { "type": "ImportAssets", "url": "fonts_en.swf", "assets": [{ "id": 4, "name": "$Main" }] },
+            { "type": "ShowFrame" }
+        ])));
+        assert_eq!(Target::Font("$Main".to_string()).resolve(&movie).unwrap(), 4);
+        assert!(Target::Font("$Title".to_string()).resolve(&movie).is_err());
+    }
+}
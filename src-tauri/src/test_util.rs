@@ -0,0 +1,33 @@
+//! Builders for movies used by unit tests.
+
+use serde_json::Value;
+use swf_fixed::Ufixed8P8;
+use swf_types::{Header, Movie, Rect, Tag};
+
+/// Reads a tag written like the `tags` of a config's new sprite.
+pub fn tag(value: Value) -> Tag {
+    serde_json::from_value(value).unwrap()
+}
+
+/// Reads a JSON array of tags, e.g. a timeline.
+pub fn tag_list(values: Value) -> Vec<Tag> {
+    serde_json::from_value(values).unwrap()
+}
+
+/// A 24 fps movie holding `tags`.
+pub fn movie(tags: Vec<Tag>) -> Movie {
+    Movie {
+        header: Header {
+            swf_version: 10,
+            frame_size: Rect {
+                x_min: 0,
+                x_max: 0,
+                y_min: 0,
+                y_max: 0,
+            },
+            frame_rate: Ufixed8P8::from_epsilons(24 << 8),
+            frame_count: 1,
+        },
+        tags,
+    }
+}
@@ -0,0 +1,536 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use swf_types::{Movie, Tag};
+
+use crate::deps::DependencyGraph;
+use crate::error::StarDeltaError;
+use crate::ids::IdAllocator;
+use crate::swf::{
+    apply_json_patch, character_tag, count_timeline_matches, is_timeline_tag, modification_matches,
+    new_element_locations, plan_new_element_ids, supported_properties, ModificationConfig, ModificationMode,
+    RemoveElements, TagModification,
+};
+use crate::target::{CharacterRef, Target};
+use crate::walk::walk_tags;
+
+/// A single problem found while resolving a config against a movie.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum ValidationIssue {
+    /// No character with this ID exists in the movie.
+    #[serde(rename_all = "camelCase")]
+    UnmatchedId { location: String, expected: String, id: u16 },
+    /// The ID exists but is defined by a different tag type than the config expects.
+    #[serde(rename_all = "camelCase")]
+    TagMismatch { location: String, expected: String, found: String, id: u16 },
+    /// A `json_patch` operation fails against the movie (including a failed `test`), or the
+    /// patched movie no longer reads back as a SWF.
+    #[serde(rename_all = "camelCase")]
+    JsonPatchFailed { location: String, path: String, message: String },
+    /// A class, export or instance selector names no character.
+    #[serde(rename_all = "camelCase")]
+    UnresolvedTarget { location: String, target: String },
+    /// A removal would leave characters that require it broken and `cascade` is not set.
+    #[serde(rename_all = "camelCase")]
+    RemovalBlocked { location: String, id: u16, dependents: Vec<u16> },
+    /// A new element asks for an ID that is already defined, imported or taken by another new element.
+    #[serde(rename_all = "camelCase")]
+    IdCollision { location: String, id: u16, existing: String },
+    /// Two new elements use the same `alias`.
+    #[serde(rename_all = "camelCase")]
+    DuplicateAlias { location: String, alias: String },
+    /// No tag matches a merge-mode modification.
+    #[serde(rename_all = "camelCase")]
+    UnmatchedModification { location: String, tag: String, id: u16 },
+    /// No tag of a timeline modification's type is within its `scope`.
+    #[serde(rename_all = "camelCase")]
+    UnmatchedScope { location: String, tag: String },
+    /// The movie has no tag of this type at all.
+    #[serde(rename_all = "camelCase")]
+    MissingTag { location: String, tag: String },
+    /// The tag type is not one `TagModification` knows how to modify.
+    #[serde(rename_all = "camelCase")]
+    UnsupportedTag { location: String, tag: String },
+    /// The property is present in the config but will not be applied.
+    #[serde(rename_all = "camelCase")]
+    IgnoredProperty { location: String, tag: String, property: String, reason: String },
+    /// Any other error patching would stop at, e.g. an imported ID that does not match.
+    #[serde(rename_all = "camelCase")]
+    InvalidConfig { location: String, message: String },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationIssue::UnmatchedId { location, expected, id } => {
+                write!(f, "{}: no {} with ID {}", location, expected, id)
+            }
+            ValidationIssue::TagMismatch { location, expected, found, id } => {
+                write!(f, "{}: ID {} is a {}, expected {}", location, id, found, expected)
+            }
+            ValidationIssue::JsonPatchFailed { location, path, message } => {
+                write!(f, "{}: failed at '{}': {}", location, path, message)
+            }
+            ValidationIssue::UnresolvedTarget { location, target } => {
+                write!(f, "{}: no character matches {}", location, target)
+            }
+            ValidationIssue::RemovalBlocked { location, id, dependents } => {
+                write!(f, "{}: {} is still required by {:?}, set cascade to remove them too", location, id, dependents)
+            }
+            ValidationIssue::IdCollision { location, id, existing } => {
+                write!(f, "{}: ID {} is already used by {}", location, id, existing)
+            }
+            ValidationIssue::DuplicateAlias { location, alias } => {
+                write!(f, "{}: alias '@{}' is already defined", location, alias)
+            }
+            ValidationIssue::UnmatchedModification { location, tag, id } => {
+                write!(f, "{}: no {} matches ID {}", location, tag, id)
+            }
+            ValidationIssue::UnmatchedScope { location, tag } => {
+                write!(f, "{}: no {} is within the scope", location, tag)
+            }
+            ValidationIssue::MissingTag { location, tag } => {
+                write!(f, "{}: the movie has no {}", location, tag)
+            }
+            ValidationIssue::UnsupportedTag { location, tag } => {
+                write!(f, "{}: {} cannot be modified", location, tag)
+            }
+            ValidationIssue::IgnoredProperty { location, tag, property, reason } => {
+                write!(f, "{}: '{}' on {} is ignored ({})", location, property, tag, reason)
+            }
+            ValidationIssue::InvalidConfig { location, message } => write!(f, "{}: {}", location, message),
+        }
+    }
+}
+
+/// Result of checking a modification config against a movie without applying it.
+#[derive(Debug, Default, Serialize)]
+pub struct ValidationReport {
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+struct Validator<'a> {
+    movie: &'a Movie,
+    characters: HashMap<u16, &'static str>,
+    /// IDs and aliases of the config's new elements
+    new_ids: IdAllocator,
+    /// `name` of each new font and imported asset, `None` when it comes from a font file
+    new_fonts: Vec<Option<String>>,
+    report: ValidationReport,
+}
+
+impl<'a> Validator<'a> {
+    fn new(movie: &'a Movie) -> Self {
+        let mut characters = HashMap::new();
+        walk_tags(&movie.tags, &mut |_, tag| {
+            if let Some((id, name)) = character_tag(tag) {
+                characters.insert(id, name);
+            }
+        });
+        Validator {
+            movie,
+            characters,
+            new_ids: IdAllocator::new(movie),
+            new_fonts: Vec::new(),
+            report: ValidationReport::default(),
+        }
+    }
+
+    /// Whether any tag, in any timeline, satisfies `predicate`.
+    fn any_tag(&self, predicate: impl Fn(&Tag) -> bool) -> bool {
+        let mut found = false;
+        walk_tags(&self.movie.tags, &mut |_, tag| found = found || predicate(tag));
+        found
+    }
+
+    fn push(&mut self, issue: ValidationIssue) {
+        self.report.issues.push(issue);
+    }
+
+    /// Checks that `id` is defined by one of the `expected` tag types (any type if empty).
+    fn expect_character(&mut self, location: String, id: u16, expected: &[&str]) {
+        let expected_name = if expected.is_empty() {
+            "character".to_string()
+        } else {
+            expected.join(" or ")
+        };
+        match self.characters.get(&id) {
+            Some(found) if expected.is_empty() || expected.contains(found) => {}
+            Some(found) => self.push(ValidationIssue::TagMismatch {
+                location,
+                expected: expected_name,
+                found: found.to_string(),
+                id,
+            }),
+            None => self.push(ValidationIssue::UnmatchedId {
+                location,
+                expected: expected_name,
+                id,
+            }),
+        }
+    }
+
+    /// Resolves a selector, reporting it if it matches nothing. Aliases of new elements
+    /// resolve to `None` too, as those characters are not in the movie to be checked.
+    fn resolve(&mut self, location: &str, character: &CharacterRef) -> Option<u16> {
+        match character.resolve(self.movie, self.new_ids.aliases()) {
+            Ok(_) if matches!(character, CharacterRef::Alias(_)) => None,
+            Ok(id) => Some(id),
+            Err(_) => {
+                self.push(ValidationIssue::UnresolvedTarget {
+                    location: location.to_string(),
+                    target: character.to_string(),
+                });
+                None
+            }
+        }
+    }
+
+    fn expect_tag(&mut self, location: String, tag: &str, present: impl Fn(&Tag) -> bool) {
+        if !self.any_tag(present) {
+            self.push(ValidationIssue::MissingTag {
+                location,
+                tag: tag.to_string(),
+            });
+        }
+    }
+
+    /// Resolves the scope of a timeline modification and counts the tags it would change,
+    /// as patching does.
+    fn check_timeline_scope(&mut self, location: &str, modification: &TagModification) {
+        if let Some(scope) = &modification.scope {
+            for (field, character) in [("sprite", &scope.sprite), ("character", &scope.character)] {
+                let Some(character) = character else { continue };
+                // New elements are not in the movie yet, so their timelines can't be counted
+                let Some(id) = self.resolve(&format!("{}.scope.{}", location, field), character) else {
+                    return;
+                };
+                if field == "sprite" && self.characters.get(&id) != Some(&"DefineSpriteTag") {
+                    self.expect_character(format!("{}.scope.sprite", location), id, &["DefineSpriteTag"]);
+                    return;
+                }
+            }
+        }
+        match count_timeline_matches(self.movie, modification, self.new_ids.aliases()) {
+            Ok(0) if !modification.optional => self.push(ValidationIssue::UnmatchedScope {
+                location: location.to_string(),
+                tag: modification.tag.clone(),
+            }),
+            Ok(_) => {}
+            Err(e) => self.push(ValidationIssue::InvalidConfig {
+                location: format!("{}.scope", location),
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    fn check_modification(&mut self, location: String, modification: &TagModification) {
+        let tag = modification.tag.as_str();
+        let id = modification.id;
+        if is_timeline_tag(tag) {
+            self.check_timeline_scope(&location, modification);
+            if modification.mode == ModificationMode::Fields {
+                self.check_properties(&location, modification);
+            }
+            return;
+        }
+        if modification.mode == ModificationMode::Merge {
+            // Any tag type and field can be merged, so only check that something matches
+            if !modification.optional && !self.any_tag(|t| modification_matches(t, modification)) {
+                self.push(ValidationIssue::UnmatchedModification {
+                    location,
+                    tag: tag.to_string(),
+                    id,
+                });
+            }
+            return;
+        }
+        if modification.optional && supported_properties(tag).is_some() {
+            // Optional modifications are allowed to match nothing
+            self.check_properties(&location, modification);
+            return;
+        }
+        match tag {
+            "DefineBinaryDataTag" | "DefineBitmapTag" | "DefineButtonTag" | "DefineDynamicTextTag"
+            | "DefineMorphShapeTag" | "DefineShapeTag" | "DefineSpriteTag" | "DefineTextTag" => {
+                self.expect_character(format!("{}.id", location), id, &[tag]);
+            }
+            "DefineButtonColorTransformTag" => {
+                let found = self.any_tag(|t| {
+                    matches!(t, Tag::DefineButtonColorTransform(t) if t.button_id == id)
+                });
+                if !found {
+                    self.push(ValidationIssue::UnmatchedId {
+                        location: format!("{}.id", location),
+                        expected: tag.to_string(),
+                        id,
+                    });
+                }
+            }
+            "DoAbcTag" => self.expect_tag(location.clone(), tag, |t| matches!(t, Tag::DoAbc(_))),
+            "FileAttributesTag" => {
+                self.expect_tag(location.clone(), tag, |t| matches!(t, Tag::FileAttributes(_)))
+            }
+            "SetBackgroundColorTag" => {
+                self.expect_tag(location.clone(), tag, |t| matches!(t, Tag::SetBackgroundColor(_)))
+            }
+            "SymbolClassTag" => self.expect_tag(location.clone(), tag, |t| matches!(t, Tag::SymbolClass(_))),
+            "DefineSceneAndFrameLabelDataTag" => self.expect_tag(location.clone(), tag, |t| {
+                matches!(t, Tag::DefineSceneAndFrameLabelData(_))
+            }),
+            _ => {
+                self.push(ValidationIssue::UnsupportedTag {
+                    location: format!("{}.tag", location),
+                    tag: tag.to_string(),
+                });
+                return;
+            }
+        }
+        self.check_properties(&location, modification);
+    }
+
+    fn check_properties(&mut self, location: &str, modification: &TagModification) {
+        let tag = modification.tag.as_str();
+        let supported = supported_properties(tag).unwrap_or(&[]);
+        let Some(properties) = modification.properties.as_object() else {
+            self.push(ValidationIssue::IgnoredProperty {
+                location: format!("{}.properties", location),
+                tag: tag.to_string(),
+                property: "properties".to_string(),
+                reason: "not a JSON object".to_string(),
+            });
+            return;
+        };
+
+        for key in properties.keys() {
+            let reason = if !supported.contains(&key.as_str()) {
+                Some("not supported for this tag".to_string())
+            } else if tag == "DefineShapeTag" && key != "shape" && properties.contains_key("shape") {
+                // A full `shape` replaces the whole shape, everything else is skipped
+                Some("overridden by 'shape'".to_string())
+            } else if tag == "DefineShapeTag"
+                && (key == "fillStyles" || key == "lineStyles")
+                && properties.contains_key("styles")
+            {
+                Some("overridden by 'styles'".to_string())
+            } else {
+                None
+            };
+            if let Some(reason) = reason {
+                self.push(ValidationIssue::IgnoredProperty {
+                    location: format!("{}.properties.{}", location, key),
+                    tag: tag.to_string(),
+                    property: key.clone(),
+                    reason,
+                });
+            }
+        }
+
+        if tag == "DefineDynamicTextTag" {
+            if let Some(font) = properties.get("fontId").filter(|f| !f.is_null()) {
+                let location = format!("{}.properties.fontId", location);
+                match serde_json::from_value::<CharacterRef>(font.clone()) {
+                    Ok(font) => self.check_font(location, &font),
+                    Err(e) => self.push(ValidationIssue::IgnoredProperty {
+                        location,
+                        tag: tag.to_string(),
+                        property: "fontId".to_string(),
+                        reason: e.to_string(),
+                    }),
+                }
+            }
+        }
+    }
+
+    fn check_font(&mut self, location: String, font: &CharacterRef) {
+        // New fonts don't exist in the movie yet, and may take their name from the font file
+        if let CharacterRef::Target(Target::Font(name)) = font {
+            if self.new_fonts.iter().any(|n| n.as_ref().is_none_or(|n| n == name)) {
+                return;
+            }
+        }
+        if let Some(id) = self.resolve(&location, font) {
+            let imported = |t: &Tag| matches!(t, Tag::ImportAssets(i) if i.assets.iter().any(|a| a.id == id));
+            if !self.any_tag(imported) {
+                self.expect_character(location, id, &["DefineFontTag", "DefineCffFontTag"]);
+            }
+        }
+    }
+
+    fn check_removals(&mut self, location: &str, elements: &RemoveElements) {
+        let groups: [(&str, &Option<Vec<CharacterRef>>, &[&str]); 5] = [
+            ("shapes", &elements.shapes, &["DefineShapeTag"]),
+            ("sprites", &elements.sprites, &["DefineSpriteTag"]),
+            ("texts", &elements.texts, &["DefineTextTag", "DefineDynamicTextTag"]),
+            ("buttons", &elements.buttons, &["DefineButtonTag"]),
+            ("bitmaps", &elements.bitmaps, &["DefineBitmapTag"]),
+        ];
+        let mut resolved = Vec::new();
+        for (field, characters, expected) in groups {
+            for (i, character) in characters.iter().flatten().enumerate() {
+                let location = format!("{}.{}[{}]", location, field, i);
+                if let Some(id) = self.resolve(&location, character) {
+                    resolved.push((location, id, expected));
+                }
+            }
+        }
+        let requested: Vec<u16> = resolved.iter().map(|(_, id, _)| *id).collect();
+        let graph = DependencyGraph::build(self.movie);
+        for (location, id, expected) in resolved {
+            let dependents: Vec<u16> = graph.dependents(id).filter(|d| !requested.contains(d)).collect();
+            if !elements.cascade && !dependents.is_empty() {
+                self.push(ValidationIssue::RemovalBlocked {
+                    location: location.clone(),
+                    id,
+                    dependents,
+                });
+            }
+            self.expect_character(location, id, expected);
+        }
+    }
+
+    /// Picks IDs for the config's new elements as patching would, reporting the first
+    /// explicit ID that is already taken or alias that is defined twice, and checks the
+    /// sprites they are placed in and the tags they are inserted before.
+    fn check_new_elements(&mut self, config: &ModificationConfig) {
+        match plan_new_element_ids(self.movie, config) {
+            Ok(ids) => self.new_ids = ids,
+            Err(StarDeltaError::IdCollision { id, location, existing }) => {
+                self.push(ValidationIssue::IdCollision { location, id, existing })
+            }
+            Err(StarDeltaError::DuplicateAlias { alias, location }) => {
+                self.push(ValidationIssue::DuplicateAlias { location, alias })
+            }
+            Err(e) => self.push(ValidationIssue::InvalidConfig {
+                location: "new_elements".to_string(),
+                message: e.to_string(),
+            }),
+        }
+
+        let blocks = [("swf.new_elements", &config.swf.new_elements), ("new_elements", &config.new_elements)];
+        for elements in blocks.iter().filter_map(|(_, elements)| elements.as_ref()) {
+            self.new_fonts.extend(elements.fonts.iter().flatten().map(|f| f.name.clone()));
+            let imports = elements.imports.iter().flatten().flat_map(|i| &i.assets);
+            self.new_fonts.extend(imports.map(|a| Some(a.name.clone())));
+        }
+        for (location, elements) in blocks {
+            let Some(elements) = elements else { continue };
+            for (element, place, before) in new_element_locations(elements, location) {
+                if let Some(sprite) = place.and_then(|p| p.sprite.as_ref()) {
+                    let location = format!("{}.place.sprite", element);
+                    if let Some(id) = self.resolve(&location, sprite) {
+                        self.expect_character(location, id, &["DefineSpriteTag"]);
+                    }
+                }
+                if let Some(anchor) = before.filter(|a| a.position(&self.movie.tags).is_err()) {
+                    self.push(ValidationIssue::MissingTag {
+                        location: format!("{}.before", element),
+                        tag: format!("{} with index {}", anchor.tag, anchor.index),
+                    });
+                }
+            }
+            for (i, text) in elements.texts.iter().flatten().enumerate() {
+                if let Some(font) = &text.font_id {
+                    self.check_font(format!("{}.texts[{}].font_id", location, i), font);
+                }
+            }
+        }
+    }
+
+    fn check_config(mut self, config: &ModificationConfig) -> ValidationReport {
+        self.check_new_elements(config);
+
+        for (i, source) in config.file.iter().flatten().enumerate() {
+            for (j, target) in source.shapes.iter().enumerate() {
+                let mut location = format!("file[{}].shapes[{}]", i, j);
+                if target.svg_id().is_some() {
+                    location.push_str(".shape");
+                }
+                if let Some(id) = self.resolve(&location, target.shape()) {
+                    self.expect_character(location, id, &["DefineShapeTag"]);
+                }
+            }
+        }
+
+        for (i, shape) in config.transparent.iter().flatten().enumerate() {
+            let location = format!("transparent[{}]", i);
+            if let Some(id) = self.resolve(&location, shape) {
+                self.expect_character(location, id, &["DefineShapeTag"]);
+            }
+        }
+
+        for (i, patch) in config.actionscript.iter().flatten().enumerate() {
+            for (j, binding) in patch.symbol_bindings.iter().flatten().enumerate() {
+                let location = format!("actionscript[{}].symbol_bindings[{}].symbol_id", i, j);
+                if let Some(id) = self.resolve(&location, &binding.symbol_id) {
+                    self.expect_character(location, id, &[]);
+                }
+            }
+        }
+
+        for (i, modification) in config.swf.modifications.iter().enumerate() {
+            let location = format!("swf.modifications[{}]", i);
+            match modification.resolve(self.movie, self.new_ids.aliases()) {
+                Ok(modification) => self.check_modification(location, &modification),
+                Err(_) if modification.optional => {}
+                Err(e) => {
+                    let field = if modification.target.is_some() { "target" } else { "properties" };
+                    let target = match e {
+                        StarDeltaError::UnresolvedTarget { target } => target,
+                        e => e.to_string(),
+                    };
+                    self.push(ValidationIssue::UnresolvedTarget {
+                        location: format!("{}.{}", location, field),
+                        target,
+                    });
+                }
+            }
+        }
+
+        if let Some(elements) = &config.remove_elements {
+            self.check_removals("remove_elements", elements);
+        }
+        if let Some(elements) = &config.swf.remove_elements {
+            self.check_removals("swf.remove_elements", elements);
+        }
+
+        self.report
+    }
+}
+
+/// Resolves every ID and tag reference in `config` against `movie` without modifying it.
+/// References are checked against the movie as `json_patch` leaves it, since patching runs
+/// every other step on that; if the patch fails they are checked against `movie` itself.
+pub fn validate_config(movie: &Movie, config: &ModificationConfig) -> ValidationReport {
+    let Some(patch) = &config.json_patch else {
+        return Validator::new(movie).check_config(config);
+    };
+    let mut patched = movie.clone();
+    let issue = match apply_json_patch(&mut patched, patch) {
+        Ok(()) => return Validator::new(&patched).check_config(config),
+        Err(StarDeltaError::JsonPatch { operation: Some(i), path, message }) => ValidationIssue::JsonPatchFailed {
+            location: format!("json_patch[{}]", i),
+            path,
+            message,
+        },
+        Err(StarDeltaError::JsonPatch { operation: None, path, message }) => ValidationIssue::JsonPatchFailed {
+            location: "json_patch".to_string(),
+            path,
+            message: format!("the patched movie is not a valid SWF: {}", message),
+        },
+        Err(e) => ValidationIssue::InvalidConfig {
+            location: "json_patch".to_string(),
+            message: e.to_string(),
+        },
+    };
+    let mut report = Validator::new(movie).check_config(config);
+    report.issues.insert(0, issue);
+    report
+}
@@ -0,0 +1,215 @@
+use std::fmt;
+use swf_types::{tags, Tag};
+
+use crate::error::Result;
+
+/// Where a tag sits: the chain of sprite IDs from the root timeline down to the timeline
+/// containing the tag, and the 0-based frame index within that timeline.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagPath {
+    pub sprites: Vec<u16>,
+    pub frame: usize,
+}
+
+impl TagPath {
+    /// Path of the first frame of a sprite's timeline.
+    pub fn sprite(sprite_id: u16) -> Self {
+        TagPath {
+            sprites: vec![sprite_id],
+            frame: 0,
+        }
+    }
+}
+
+impl fmt::Display for TagPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("root")?;
+        for id in &self.sprites {
+            write!(f, " > sprite {}", id)?;
+        }
+        write!(f, ", frame {}", self.frame)
+    }
+}
+
+/// Visits every tag in `tags` and, after each `DefineSprite`, every tag of its timeline,
+/// at any nesting depth.
+pub fn walk_tags<'a>(tags: &'a [Tag], f: &mut impl FnMut(&TagPath, &'a Tag)) {
+    walk_in(tags, &mut TagPath::default(), f);
+}
+
+/// [`walk_tags`] over a timeline that starts at `path`, e.g. a single sprite's tags.
+pub fn walk_tags_at<'a>(tags: &'a [Tag], mut path: TagPath, f: &mut impl FnMut(&TagPath, &'a Tag)) {
+    walk_in(tags, &mut path, f);
+}
+
+fn walk_in<'a>(tags: &'a [Tag], path: &mut TagPath, f: &mut impl FnMut(&TagPath, &'a Tag)) {
+    let frame = path.frame;
+    path.frame = 0;
+    for tag in tags {
+        f(path, tag);
+        match tag {
+            Tag::ShowFrame { .. } => path.frame += 1,
+            Tag::DefineSprite(sprite) => {
+                path.sprites.push(sprite.id);
+                walk_in(&sprite.tags, path, f);
+                path.sprites.pop();
+            }
+            _ => {}
+        }
+    }
+    path.frame = frame;
+}
+
+/// Like [`walk_tags`], but `f` may modify or replace each tag. A sprite's timeline is
+/// walked after `f` has seen the sprite itself, so replaced timelines are visited too.
+pub fn walk_tags_mut(tags: &mut [Tag], f: &mut impl FnMut(&TagPath, &mut Tag) -> Result<()>) -> Result<()> {
+    walk_tags_mut_at(tags, TagPath::default(), f)
+}
+
+/// [`walk_tags_mut`] over a timeline that starts at `path`, e.g. a single sprite's tags.
+pub fn walk_tags_mut_at(
+    tags: &mut [Tag],
+    mut path: TagPath,
+    f: &mut impl FnMut(&TagPath, &mut Tag) -> Result<()>,
+) -> Result<()> {
+    walk_mut_in(tags, &mut path, f)
+}
+
+fn walk_mut_in(
+    tags: &mut [Tag],
+    path: &mut TagPath,
+    f: &mut impl FnMut(&TagPath, &mut Tag) -> Result<()>,
+) -> Result<()> {
+    let frame = path.frame;
+    path.frame = 0;
+    for tag in tags.iter_mut() {
+        f(path, tag)?;
+        match tag {
+            Tag::ShowFrame { .. } => path.frame += 1,
+            Tag::DefineSprite(sprite) => {
+                path.sprites.push(sprite.id);
+                walk_mut_in(&mut sprite.tags, path, f)?;
+                path.sprites.pop();
+            }
+            _ => {}
+        }
+    }
+    path.frame = frame;
+    Ok(())
+}
+
+/// Removes every tag, in any timeline, for which `keep` returns `false`. Removing a
+/// `DefineSprite` removes its whole timeline without visiting it.
+pub fn retain_tags(tags: &mut Vec<Tag>, keep: &mut impl FnMut(&TagPath, &Tag) -> bool) {
+    retain_in(tags, &mut TagPath::default(), keep);
+}
+
+fn retain_in(tags: &mut Vec<Tag>, path: &mut TagPath, keep: &mut impl FnMut(&TagPath, &Tag) -> bool) {
+    let frame = path.frame;
+    path.frame = 0;
+    tags.retain_mut(|tag| {
+        if !keep(path, tag) {
+            return false;
+        }
+        match tag {
+            Tag::ShowFrame { .. } => path.frame += 1,
+            Tag::DefineSprite(sprite) => {
+                path.sprites.push(sprite.id);
+                retain_in(&mut sprite.tags, path, keep);
+                path.sprites.pop();
+            }
+            _ => {}
+        }
+        true
+    });
+    path.frame = frame;
+}
+
+/// Finds a sprite definition anywhere in `tags`.
+pub fn find_sprite(tags: &[Tag], id: u16) -> Option<&tags::DefineSprite> {
+    for tag in tags {
+        if let Tag::DefineSprite(sprite) = tag {
+            if sprite.id == id {
+                return Some(sprite);
+            }
+            if let Some(found) = find_sprite(&sprite.tags, id) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// Mutable version of [`find_sprite`].
+pub fn find_sprite_mut(tags: &mut [Tag], id: u16) -> Option<&mut tags::DefineSprite> {
+    for tag in tags.iter_mut() {
+        match tag {
+            Tag::DefineSprite(sprite) if sprite.id == id => return Some(sprite),
+            Tag::DefineSprite(sprite) => {
+                if let Some(found) = find_sprite_mut(&mut sprite.tags, id) {
+                    return Some(found);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    use crate::test_util::tag_list;
+
+    /// Sprite 1 defines sprite 2 between its two frames, and the root timeline has two frames.
+    fn nested() -> Vec<Tag> {
+        tag_list(json!([
+            {
+                "type": "DefineSprite",
+                "id": 1,
+                "frame_count": 2,
+                "tags": [
+                    { "type": "ShowFrame" },
+                    { "type": "DefineSprite", "id": 2, "frame_count": 1, "tags": [{ "type": "ShowFrame" }] },
+                    { "type": "ShowFrame" }
+                ]
+            },
+            { "type": "ShowFrame" },
+            { "type": "ShowFrame" }
+        ]))
+    }
+
+    #[test]
+    fn walk_reports_sprite_chain_and_frame() {
+        let tags = nested();
+        let mut paths = Vec::new();
+        walk_tags(&tags, &mut |path, _| paths.push(path.to_string()));
+        assert_eq!(
+            paths,
+            [
+                "root, frame 0",
+                "root > sprite 1, frame 0",
+                "root > sprite 1, frame 1",
+                "root > sprite 1 > sprite 2, frame 0",
+                "root > sprite 1, frame 1",
+                "root, frame 0",
+                "root, frame 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn retain_drops_whole_sprites() {
+        let mut tags = nested();
+        let mut visited = 0;
+        retain_tags(&mut tags, &mut |_, tag| {
+            visited += 1;
+            !matches!(tag, Tag::DefineSprite(sprite) if sprite.id == 2)
+        });
+        assert_eq!(visited, 6);
+        assert!(find_sprite(&tags, 2).is_none());
+        assert_eq!(find_sprite(&tags, 1).unwrap().tags.len(), 2);
+    }
+}
@@ -1,9 +1,12 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
+#[cfg(feature = "gui")]
 use tauri::command;
 use xdelta3::{decode, encode};
 
+use crate::error::{Result, StarDeltaError};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CreatePatchArgs {
     pub original_file_path: String,
@@ -20,52 +23,60 @@ pub struct ApplyPatchArgs {
     pub file_to_patch_name: String,
 }
 
-#[command]
-pub fn create_patch(args: CreatePatchArgs) -> Result<(), String> {
+/// Encodes an xdelta3 patch that turns `original` into `edited`.
+pub fn diff_bytes(original: &[u8], edited: &[u8]) -> Result<Vec<u8>> {
+    encode(edited, original).ok_or_else(|| {
+        log::error!("Encoding failed");
+        StarDeltaError::Xdelta { operation: "encode".to_string() }
+    })
+}
+
+/// Applies an xdelta3 patch to `source` and returns the patched bytes.
+pub fn apply_bytes(source: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    decode(patch, source).ok_or_else(|| {
+        log::error!("Decoding failed");
+        StarDeltaError::Xdelta { operation: "decode".to_string() }
+    })
+}
+
+#[cfg_attr(feature = "gui", command)]
+pub fn create_patch(args: CreatePatchArgs) -> Result<()> {
     log::trace!("Creating patch with args: {:?}", args);
     let original = fs::read(&args.original_file_path).map_err(|e| {
         log::error!("Failed to read original file: {}", e);
-        e.to_string()
+        StarDeltaError::io("Failed to read original file", &args.original_file_path, e)
     })?;
     let edited = fs::read(&args.edited_file_path).map_err(|e| {
         log::error!("Failed to read edited file: {}", e);
-        e.to_string()
-    })?;
-    let patch = encode(&edited, &original).ok_or_else(|| {
-        let msg = "Encoding failed".to_string();
-        log::error!("{}", msg);
-        msg
+        StarDeltaError::io("Failed to read edited file", &args.edited_file_path, e)
     })?;
+    let patch = diff_bytes(&original, &edited)?;
     let output_path =
         PathBuf::from(&args.output_dir).join(format!("{}.xdelta", args.original_file_name));
     fs::write(&output_path, &patch).map_err(|e| {
         log::error!("Failed to write patch file: {}", e);
-        e.to_string()
+        StarDeltaError::io("Failed to write patch file", &output_path, e)
     })?;
     log::info!("Patch created successfully at {:?}", output_path);
     Ok(())
 }
 
-#[command]
-pub fn apply_patch(args: ApplyPatchArgs) -> Result<(), String> {
+#[cfg_attr(feature = "gui", command)]
+pub fn apply_patch(args: ApplyPatchArgs) -> Result<()> {
     log::trace!("Applying patch with args: {:?}", args);
     let file_to_patch = fs::read(&args.file_to_patch_path).map_err(|e| {
         log::error!("Failed to read file to patch: {}", e);
-        e.to_string()
+        StarDeltaError::io("Failed to read file to patch", &args.file_to_patch_path, e)
     })?;
     let patch = fs::read(&args.patch_file_path).map_err(|e| {
         log::error!("Failed to read patch file: {}", e);
-        e.to_string()
-    })?;
-    let decoded = decode(&patch, &file_to_patch).ok_or_else(|| {
-        let msg = "Decoding failed".to_string();
-        log::error!("{}", msg);
-        msg
+        StarDeltaError::io("Failed to read patch file", &args.patch_file_path, e)
     })?;
+    let decoded = apply_bytes(&file_to_patch, &patch)?;
     let output_path = PathBuf::from(&args.output_dir).join(&args.file_to_patch_name);
     fs::write(&output_path, &decoded).map_err(|e| {
         log::error!("Failed to write patched file: {}", e);
-        e.to_string()
+        StarDeltaError::io("Failed to write patched file", &output_path, e)
     })?;
     log::info!("Patch applied successfully at {:?}", output_path);
     Ok(())
@@ -12,6 +12,7 @@ import { Label } from "@/components/ui/label"
 import { NavLink } from "react-router"
 import { Logo } from "@/components/ui/logo"
 import { toast } from "sonner"
+import { errorMessage } from "@/lib/utils"
 
 const MAX_FILE_SIZE = 100 * 1024 * 1024 // 100MB
 
@@ -30,6 +31,47 @@ interface SwfMapping {
   swf_path: string;
 }
 
+interface ModificationCount {
+  location: string;
+  tag: string;
+  id: number;
+  optional: boolean;
+  touched: number;
+}
+
+interface PatchSummary {
+  modifications: ModificationCount[];
+  removed: Array<{ id: number; tag: string }>;
+  assigned: Array<{ location: string; tag: string; id: number }>;
+}
+
+interface ProcessedFile {
+  outputPath: string;
+  summary: PatchSummary;
+}
+
+// One line describing what a patch did, e.g. "12 modification(s), 3 tag(s) changed"
+function describeSummary(summary: PatchSummary) {
+  const touched = summary.modifications.reduce((total, m) => total + m.touched, 0)
+  const parts = [`${summary.modifications.length} modification(s)`, `${touched} tag(s) changed`]
+  if (summary.removed.length > 0) parts.push(`${summary.removed.length} character(s) removed`)
+  if (summary.assigned.length > 0) parts.push(`${summary.assigned.length} ID(s) assigned`)
+  return parts.join(", ")
+}
+
+// Modifications that matched nothing and are not marked optional
+function unmatchedModifications(summary: PatchSummary) {
+  return summary.modifications.filter(m => m.touched === 0 && !m.optional)
+}
+
+function warnUnmatched(summary: PatchSummary, file?: string) {
+  const unmatched = unmatchedModifications(summary)
+  if (unmatched.length === 0) return
+  toast.warning(`${unmatched.length} modification(s) matched no tags${file ? ` in ${file}` : ""}`, {
+    description: unmatched.map(m => `${m.location} (${m.tag} ${m.id})`).join("\n"),
+  })
+}
+
 export function SwfPatcher() {
   // End-user states
   const [originalSwfPath, setOriginalSwfPath] = useState("")
@@ -126,30 +168,18 @@ export function SwfPatcher() {
       setIsApplyingMod(true)
       toast.loading("Applying mod...", { id: "apply-mod" })
 
-      // First convert original SWF to temporary JSON
-      const tempJsonPath = originalSwfPath + ".temp.json"
-      await invoke("convert_swf_to_json", {
+      // Parse, modify and re-emit the SWF in one step
+      const summary = await invoke<PatchSummary>("patch_swf", {
         swfPath: originalSwfPath,
-        jsonPath: tempJsonPath
+        configPath: modJsonPath,
+        outputPath: outputPath
       })
 
-      // Apply the mod's modifications
-      await invoke("apply_json_modifications", {
-        swfJsonPath: tempJsonPath,
-        configJsonPath: modJsonPath,
-        outputJsonPath: tempJsonPath
-      })
-
-      // Convert back to SWF
-      await invoke("convert_json_to_swf", {
-        jsonPath: tempJsonPath,
-        swfPath: outputPath
-      })
-
-      toast.success("Mod applied successfully!", { id: "apply-mod" })
+      toast.success("Mod applied successfully!", { id: "apply-mod", description: describeSummary(summary) })
+      warnUnmatched(summary)
     } catch (err) {
       console.error("Error applying mod:", err)
-      toast.error(err instanceof Error ? err.message : "Failed to apply mod", { id: "apply-mod" })
+      toast.error(errorMessage(err, "Failed to apply mod"), { id: "apply-mod" })
     } finally {
       setIsApplyingMod(false)
     }
@@ -170,7 +200,7 @@ export function SwfPatcher() {
 
       toast.success("SWF exported to JSON successfully! You can now create your modification JSON file.", { id: "export-json" })
     } catch (err) {
-      toast.error(err instanceof Error ? err.message : "Failed to export SWF to JSON", { id: "export-json" })
+      toast.error(errorMessage(err, "Failed to export SWF to JSON"), { id: "export-json" })
     } finally {
       setIsExportingJson(false)
     }
@@ -263,7 +293,7 @@ export function SwfPatcher() {
     try {
       setIsBatchProcessing(true)
 
-      const result = await invoke("batch_process_swf", {
+      const processed = await invoke<ProcessedFile[]>("batch_process_swf", {
         config: {
           config_file: batchConfigPath,
           output_directory: batchOutputDir,
@@ -272,11 +302,13 @@ export function SwfPatcher() {
         }
       })
 
-      toast.success("Batch processing completed successfully")
-      console.log("Processed files:", result)
+      toast.success("Batch processing completed successfully", {
+        description: processed.map(file => `${file.outputPath}: ${describeSummary(file.summary)}`).join("\n"),
+      })
+      processed.forEach(file => warnUnmatched(file.summary, file.outputPath))
     } catch (error) {
       console.error("Batch processing failed:", error)
-      toast.error("Batch processing failed")
+      toast.error(errorMessage(error, "Batch processing failed"))
     } finally {
       setIsBatchProcessing(false)
     }
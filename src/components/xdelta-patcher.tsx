@@ -12,6 +12,7 @@ import { Label } from "@/components/ui/label"
 import { NavLink } from "react-router"
 import { Logo } from "@/components/ui/logo"
 import { toast } from "sonner"
+import { errorMessage } from "@/lib/utils"
 import { Window, PhysicalSize } from "@tauri-apps/api/window"
 
 const MAX_FILE_SIZE = 100 * 1024 * 1024 // 100MB
@@ -116,7 +117,7 @@ export function XdeltaPatcher() {
 
       toast.success("Patch applied successfully!", { id: "apply-patch" })
     } catch (err) {
-      toast.error(err instanceof Error ? err.message : "Failed to apply patch", { id: "apply-patch" })
+      toast.error(errorMessage(err, "Failed to apply patch"), { id: "apply-patch" })
     } finally {
       setIsApplyingPatch(false)
     }
@@ -139,7 +140,7 @@ export function XdeltaPatcher() {
 
       toast.success("Patch created successfully!", { id: "create-patch" })
     } catch (err) {
-      toast.error(err instanceof Error ? err.message : "Failed to create patch", { id: "create-patch" })
+      toast.error(errorMessage(err, "Failed to create patch"), { id: "create-patch" })
     } finally {
       setIsCreatingPatch(false)
     }
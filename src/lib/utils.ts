@@ -4,3 +4,27 @@ import { twMerge } from "tailwind-merge";
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs));
 }
+
+/** Error object returned by the Rust backend (`StarDeltaError`). */
+export interface BackendError {
+  kind: string;
+  message: string;
+  [detail: string]: unknown;
+}
+
+export function isBackendError(err: unknown): err is BackendError {
+  return (
+    typeof err === "object" &&
+    err !== null &&
+    typeof (err as BackendError).kind === "string" &&
+    typeof (err as BackendError).message === "string"
+  );
+}
+
+/** Readable message for anything thrown by the UI or rejected by a Tauri command. */
+export function errorMessage(err: unknown, fallback: string): string {
+  if (err instanceof Error) return err.message;
+  if (isBackendError(err)) return err.message;
+  if (typeof err === "string") return err;
+  return fallback;
+}